@@ -14,14 +14,19 @@ const INDENT: f32 = 32.;
 const PARAGRAPH_SPACING: f32 = 14.;
 const WIDTH: f32 = 540.;
 
+/// Tag of glyphs outside links, other glyphs are tagged with their link index + 1.
+pub const TAG_NO_LINK: u32 = 0;
+
 pub struct Display<'font> {
     glyphs: Vec<gfx_glyph::LayoutGlyph<'font>>,
     lines: Vec<Line>,
+    rects: Vec<gfx_glyph::LayoutRect>,
 }
 
 struct Line {
     bounds_y: std::ops::Range<f32>,
     glyphs: std::ops::Range<usize>,
+    rects: std::ops::Range<usize>,
 }
 
 impl<'font> Display<'font> {
@@ -32,7 +37,11 @@ impl<'font> Display<'font> {
         }
     }
 
-    pub fn clip(&self, bound_y_min: f32, bound_y_max: f32) -> &[gfx_glyph::LayoutGlyph] {
+    pub fn clip(
+        &self,
+        bound_y_min: f32,
+        bound_y_max: f32,
+    ) -> (&[gfx_glyph::LayoutGlyph], &[gfx_glyph::LayoutRect]) {
         let end_line_index = self
             .lines
             .binary_search_by(|line| {
@@ -53,11 +62,14 @@ impl<'font> Display<'font> {
             })
             .unwrap_err();
         if end_line_index > start_line_index {
-            let start_glyph_index = self.lines[start_line_index].glyphs.start;
-            let end_glyph_index = self.lines[end_line_index - 1].glyphs.end;
-            &self.glyphs[start_glyph_index..end_glyph_index]
+            let start_line = &self.lines[start_line_index];
+            let end_line = &self.lines[end_line_index - 1];
+            (
+                &self.glyphs[start_line.glyphs.start..end_line.glyphs.end],
+                &self.rects[start_line.rects.start..end_line.rects.end],
+            )
         } else {
-            &[]
+            (&[], &[])
         }
     }
 }
@@ -65,6 +77,7 @@ impl<'font> Display<'font> {
 pub fn display<'font>(
     document: &Vec<Block>,
     fonts: &[gfx_glyph::Font<'font>],
+    line_metrics: &gfx_glyph::LineMetrics,
     position_x: f32,
     mut position_y: f32,
     scale: f32,
@@ -72,6 +85,7 @@ pub fn display<'font>(
     let mut display = Display {
         glyphs: vec![],
         lines: vec![],
+        rects: vec![],
     };
     let v_metrics = fonts[0].v_metrics(gfx_glyph::Scale::uniform(1.));
     for block in document.iter() {
@@ -112,7 +126,7 @@ pub fn display<'font>(
                                 wrap = true;
                                 break;
                             }
-                            Span::Text { class, text } => {
+                            Span::Text { class, text, .. } => {
                                 let text = if span_index == 0 {
                                     &text[start_point..]
                                 } else {
@@ -168,6 +182,7 @@ pub fn display<'font>(
                     let baseline_position_y = position_y + (block_scale * v_metrics.ascent).ceil();
                     let mut caret_position_x = position_x + left_margin;
                     let glyph_count_before_line = display.glyphs.len();
+                    let rect_count_before_line = display.rects.len();
                     if display_bullet {
                         display_bullet = false;
                         display.glyphs.push(gfx_glyph::LayoutGlyph {
@@ -180,6 +195,9 @@ pub fn display<'font>(
                                     x: position_x + 0.5 * INDENT,
                                     y: baseline_position_y,
                                 }),
+                            palette_index: None,
+                            tag: TAG_NO_LINK,
+                            z: None,
                         });
                     }
                     let mut last_font_id = 0;
@@ -187,12 +205,13 @@ pub fn display<'font>(
                     for (span_index, span) in content.iter().enumerate() {
                         match span {
                             Span::LineBreak => break,
-                            Span::Text { class, text } => {
+                            Span::Text { class, link, text } => {
                                 let text = if span_index == 0 {
                                     &text[start_point..]
                                 } else {
                                     &text[..]
                                 };
+                                let tag = link.map_or(TAG_NO_LINK, |link| link as u32 + 1);
                                 let text = if span_index == break_point.0 {
                                     &text[..break_point.1]
                                 } else {
@@ -221,6 +240,7 @@ pub fn display<'font>(
                                     last_font_id = font_id;
                                 }
                                 let font = &fonts[font_id];
+                                let span_start_x = caret_position_x;
                                 for character in text.chars() {
                                     let glyph = font
                                         .glyph(map_character(character))
@@ -242,6 +262,23 @@ pub fn display<'font>(
                                             x: glyph_position_x,
                                             y: baseline_position_y,
                                         }),
+                                        palette_index: None,
+                                        tag,
+                                        z: None,
+                                    });
+                                }
+                                if color == COLOR_LINK {
+                                    display.rects.push(gfx_glyph::LayoutRect {
+                                        color,
+                                        palette_index: None,
+                                        rect: line_metrics.underline(
+                                            gfx_glyph::Scale::uniform(block_scale),
+                                            gfx_glyph::Point {
+                                                x: span_start_x,
+                                                y: baseline_position_y,
+                                            },
+                                            caret_position_x,
+                                        ),
                                     });
                                 }
                                 if span_index == break_point.0 {
@@ -253,6 +290,7 @@ pub fn display<'font>(
                     display.lines.push(Line {
                         bounds_y: position_y..baseline_position_y - block_scale * v_metrics.descent,
                         glyphs: glyph_count_before_line..display.glyphs.len(),
+                        rects: rect_count_before_line..display.rects.len(),
                     });
                     position_y +=
                         block_scale * (v_metrics.ascent - v_metrics.descent + v_metrics.line_gap);
@@ -265,7 +303,7 @@ pub fn display<'font>(
                         content = &content[break_point.0..];
                         start_point = break_point.1;
                     }
-                    while let Some(Span::Text { class: _, text }) = content.first() {
+                    while let Some(Span::Text { text, .. }) = content.first() {
                         let trimmed_text = &text[start_point..].trim_left();
                         if !trimmed_text.is_empty() {
                             start_point = text.len() - trimmed_text.len();
@@ -300,12 +338,16 @@ pub fn display<'font>(
                             x: glyph_position_x,
                             y: baseline_position_y,
                         }),
+                        palette_index: None,
+                        tag: TAG_NO_LINK,
+                        z: None,
                     });
                 }
                 display.lines.push(Line {
                     bounds_y: position_y
                         ..baseline_position_y - FONT_SIZE_REGULAR * v_metrics.descent,
                     glyphs: glyph_count_before_line..display.glyphs.len(),
+                    rects: display.rects.len()..display.rects.len(),
                 });
                 position_y += FONT_SIZE_REGULAR
                     * (v_metrics.ascent - v_metrics.descent + v_metrics.line_gap)
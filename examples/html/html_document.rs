@@ -29,10 +29,21 @@ pub enum BlockClass {
     Preformatted,
 }
 
+#[derive(Debug)]
+pub struct Document {
+    pub blocks: Vec<Block>,
+    /// Link targets, indexed by `Span::Text::link`
+    pub links: Vec<String>,
+}
+
 #[derive(Debug)]
 pub enum Span {
     LineBreak,
-    Text { class: SpanClass, text: String },
+    Text {
+        class: SpanClass,
+        link: Option<usize>,
+        text: String,
+    },
 }
 
 #[derive(Debug)]
@@ -53,6 +64,7 @@ struct State {
     current_block_class: BlockClass,
     current_spans: Option<Vec<Span>>,
     current_text: Option<String>,
+    links: Vec<String>,
     style: Style,
 }
 
@@ -60,20 +72,22 @@ struct Style {
     bold: bool,
     code: bool,
     italic: bool,
-    link: bool,
+    /// Index of the link target
+    link: Option<usize>,
 }
 
-pub fn parse(html: &str) -> Vec<Block> {
+pub fn parse(html: &str) -> Document {
     let mut state = State {
         blocks: vec![],
         current_block_class: BlockClass::Paragraph,
         current_spans: None,
         current_text: None,
+        links: vec![],
         style: Style {
             bold: false,
             code: false,
             italic: false,
-            link: false,
+            link: None,
         },
     };
     let document = html5ever::parse_fragment(
@@ -90,7 +104,10 @@ pub fn parse(html: &str) -> Vec<Block> {
     .document;
     deep_iter(&document, &mut state);
     flush_block(&mut state);
-    state.blocks
+    Document {
+        blocks: state.blocks,
+        links: state.links,
+    }
 }
 
 fn deep_iter(node: &Handle, state: &mut State) {
@@ -104,19 +121,24 @@ fn deep_iter(node: &Handle, state: &mut State) {
                 if name.ns == ns!(html) {
                     match name.local {
                         local_name!("a") => {
-                            if state.style.link
-                                || !attrs.borrow().iter().any(|ref attribute| {
+                            let href = attrs
+                                .borrow()
+                                .iter()
+                                .find(|attribute| {
                                     attribute.name.ns == ns!()
                                         && attribute.name.local == local_name!("href")
                                 })
-                            {
-                                deep_iter(node, state);
-                            } else {
-                                flush_span(state);
-                                state.style.link = true;
-                                deep_iter(node, state);
-                                flush_span(state);
-                                state.style.link = false;
+                                .map(|attribute| attribute.value.to_string());
+                            match href {
+                                Some(href) if state.style.link.is_none() => {
+                                    flush_span(state);
+                                    state.style.link = Some(state.links.len());
+                                    state.links.push(href);
+                                    deep_iter(node, state);
+                                    flush_span(state);
+                                    state.style.link = None;
+                                }
+                                _ => deep_iter(node, state),
                             }
                         }
                         local_name!("b") | local_name!("strong") if !state.style.bold => {
@@ -227,6 +249,7 @@ fn flush_block(state: &mut State) {
                 if !text.is_empty() {
                     state.current_spans = Some(vec![Span::Text {
                         class: get_span_class(&state.style),
+                        link: state.style.link,
                         text: text.to_owned(),
                     }]);
                 }
@@ -236,6 +259,7 @@ fn flush_block(state: &mut State) {
                 if !text.is_empty() {
                     spans.push(Span::Text {
                         class: get_span_class(&state.style),
+                        link: state.style.link,
                         text: text.to_owned(),
                     });
                 }
@@ -258,6 +282,7 @@ fn flush_span(state: &mut State) {
                 if !text.is_empty() {
                     state.current_spans = Some(vec![Span::Text {
                         class: get_span_class(&state.style),
+                        link: state.style.link,
                         text: text.to_owned(),
                     }]);
                 }
@@ -265,6 +290,7 @@ fn flush_span(state: &mut State) {
             Some(ref mut spans) => {
                 spans.push(Span::Text {
                     class: get_span_class(&state.style),
+                    link: state.style.link,
                     text,
                 });
             }
@@ -276,7 +302,7 @@ fn get_span_class(style: &Style) -> SpanClass {
     if style.code {
         SpanClass::Code
     } else {
-        match (style.bold, style.italic, style.link) {
+        match (style.bold, style.italic, style.link.is_some()) {
             (false, false, false) => SpanClass::Regular,
             (false, false, true) => SpanClass::Link,
             (false, true, false) => SpanClass::Italic,
@@ -43,21 +43,35 @@ fn main() {
             eprintln!("Failed to read font the file {:?}: {}\nYou can change the path in main.rs to load fonts from another location.", path, error);
             std::process::exit(1);
         });
-        gfx_glyph::Font::from_bytes(file_contents).unwrap_or_else(|error| {
+        let line_metrics = gfx_glyph::LineMetrics::parse(&file_contents);
+        let font = gfx_glyph::Font::from_bytes(file_contents).unwrap_or_else(|error| {
             eprintln!("Failed to parse font from the file {:?}: {}", path, error);
             std::process::exit(1);
-        })
+        });
+        (font, line_metrics)
     };
+    let (regular_font, line_metrics) = load_font("/usr/share/fonts/truetype/dejavu/DejaVuSans.ttf");
+    let line_metrics = line_metrics.unwrap_or_else(|| {
+        eprintln!("Failed to read underline metrics of the regular font");
+        std::process::exit(1);
+    });
     let mut glyph_brush = gfx_glyph::GlyphBrushBuilder::using_fonts(vec![
-        load_font("/usr/share/fonts/truetype/dejavu/DejaVuSans.ttf"),
-        load_font("/usr/share/fonts/truetype/dejavu/DejaVuSans-Bold.ttf"),
-        load_font("/usr/share/fonts/truetype/dejavu/DejaVuSans-BoldOblique.ttf"),
-        load_font("/usr/share/fonts/truetype/dejavu/DejaVuSans-Oblique.ttf"),
-        load_font("/usr/share/fonts/truetype/dejavu/DejaVuSansMono.ttf"),
+        regular_font,
+        load_font("/usr/share/fonts/truetype/dejavu/DejaVuSans-Bold.ttf").0,
+        load_font("/usr/share/fonts/truetype/dejavu/DejaVuSans-BoldOblique.ttf").0,
+        load_font("/usr/share/fonts/truetype/dejavu/DejaVuSans-Oblique.ttf").0,
+        load_font("/usr/share/fonts/truetype/dejavu/DejaVuSansMono.ttf").0,
     ])
     .build(factory.clone());
     let document = html_document::parse(&document);
-    let display = display_document::display(&document, glyph_brush.fonts(), -270., MARGIN, 1.);
+    let display = display_document::display(
+        &document.blocks,
+        glyph_brush.fonts(),
+        &line_metrics,
+        -270.,
+        MARGIN,
+        1.,
+    );
     let connection_number = {
         let plain_window = window.window();
         let connection = plain_window.get_xlib_xconnection().unwrap();
@@ -73,6 +87,7 @@ fn main() {
         mio::PollOpt::edge(),
     )
     .unwrap();
+    let mut cursor_position = None;
     let mut paint = true;
     let mut running = true;
     let mut scroll = 0.;
@@ -83,6 +98,28 @@ fn main() {
                 window_id: _,
             } => match event {
                 WindowEvent::CloseRequested => running = false,
+                WindowEvent::CursorMoved { position, .. } => {
+                    let position = position.to_physical(window.get_hidpi_factor());
+                    cursor_position = Some(gfx_glyph::Point {
+                        x: position.x as f32,
+                        y: position.y as f32,
+                    });
+                }
+                WindowEvent::CursorLeft { .. } => cursor_position = None,
+                WindowEvent::MouseInput {
+                    state: glutin::ElementState::Pressed,
+                    button: glutin::MouseButton::Left,
+                    ..
+                } => {
+                    // glyphs are tagged with the index + 1 of the link they're in
+                    let link = cursor_position
+                        .and_then(|position| glyph_brush.hit_test(position))
+                        .filter(|hit| hit.tag != display_document::TAG_NO_LINK)
+                        .map(|hit| &document.links[hit.tag as usize - 1]);
+                    if let Some(link) = link {
+                        println!("Clicked link {}", link);
+                    }
+                }
                 WindowEvent::MouseWheel { delta, .. } => {
                     let new_scroll = (scroll
                         - match delta {
@@ -125,6 +162,7 @@ fn main() {
             x: (0.5 * window_size_x as f32).round(),
             y: -scroll,
         };
+        let (glyphs, rects) = display.clip(scroll, scroll + window_size_y as f32);
         glyph_brush.queue_section(gfx_glyph::Section {
             bounds: gfx_glyph::Rect {
                 max: gfx_glyph::Point {
@@ -133,12 +171,13 @@ fn main() {
                 },
                 min: gfx_glyph::Point { x: 0., y: 0. },
             },
-            glyphs: display
-                .clip(scroll, scroll + window_size_y as f32)
+            glyphs: glyphs
                 .iter()
                 .map(|glyph| glyph.translated(offset))
                 .collect(),
+            rects: rects.iter().map(|rect| rect.translated(offset)).collect(),
             z: 0.,
+            ..Default::default()
         });
         glyph_brush
             .draw_queued(&mut encoder, &color_view, &depth_view)
@@ -0,0 +1,160 @@
+//! Drawing queued sections once into their own texture, so static text can be composited as
+//! an ordinary sprite instead of being queued every frame.
+use super::*;
+
+type BakedForm = format::Srgba8;
+type BakedSurface = <BakedForm as format::Formatted>::Surface;
+/// With a stencil buffer for brushes using a [`StencilMode`](enum.StencilMode.html)
+type BakedDepthForm = format::DepthStencil;
+
+/// Blends colours "over" what's drawn, premultiplied by alpha.
+const PREMULTIPLIED_OVER: gfx::state::Blend = gfx::state::Blend {
+    color: gfx::state::BlendChannel {
+        equation: gfx::state::Equation::Add,
+        source: gfx::state::Factor::ZeroPlus(gfx::state::BlendValue::SourceAlpha),
+        destination: gfx::state::Factor::OneMinus(gfx::state::BlendValue::SourceAlpha),
+    },
+    alpha: gfx::state::BlendChannel {
+        equation: gfx::state::Equation::Add,
+        source: gfx::state::Factor::One,
+        destination: gfx::state::Factor::OneMinus(gfx::state::BlendValue::SourceAlpha),
+    },
+};
+
+/// Texture containing sections drawn by
+/// [`GlyphBrush::bake_to_texture`](struct.GlyphBrush.html#method.bake_to_texture).
+///
+/// Colours are premultiplied by alpha, whatever the brush's
+/// [blend](struct.GlyphBrushBuilder.html#method.blend), so should be composited with
+/// premultiplied alpha blending.
+pub struct BakedText<R: gfx::Resources> {
+    /// Pixel position of the texture's top left in the sections' coordinates
+    pub position: Point<f32>,
+    /// Width & height in pixels
+    pub size: (u32, u32),
+    pub texture: handle::Texture<R, BakedSurface>,
+    pub view: handle::ShaderResourceView<R, [f32; 4]>,
+}
+
+impl<'font, R: gfx::Resources, F: gfx::Factory<R>> GlyphBrush<'font, R, F> {
+    /// Draws all queued sections into a new texture sized to their pixel bounds, clipped to
+    /// each section's bounds. Returns `None`, leaving the sections queued, if nothing queued
+    /// would be visible. Fails, leaving the sections queued, if the bounds are larger than the
+    /// maximum texture size.
+    ///
+    /// Sections are drawn blending premultiplied colours over each other rather than with the
+    /// brush's blend.
+    ///
+    /// The draw cache is cleared, so [`draw_cached`](#method.draw_cached) draws nothing until
+    /// the next [`draw_queued`](#method.draw_queued).
+    pub fn bake_to_texture(
+        &mut self,
+        encoder: &mut gfx::Encoder<R, impl gfx::CommandBuffer<R>>,
+    ) -> Result<Option<BakedText<R>>, String> {
+        // colour bitmaps are sized by their images, so must be decoded to find the bounds
+        let sections: Vec<_> = self.sections.drain(..).collect();
+        let cached = self.cache_color_bitmaps(&sections);
+        self.sections = sections;
+        cached?;
+
+        let bounds = match self.queued_pixel_bounds() {
+            Some(bounds) => bounds,
+            None => return Ok(None),
+        };
+        let position = point(bounds.min.x.floor(), bounds.min.y.floor());
+        let size = (
+            (bounds.max.x.ceil() - position.x) as u32,
+            (bounds.max.y.ceil() - position.y) as u32,
+        );
+        let max_size = self
+            .factory
+            .get_capabilities()
+            .max_texture_size
+            .min(usize::from(texture::Size::MAX));
+        if size.0 as usize > max_size || size.1 as usize > max_size {
+            return Err(format!(
+                "{}x{} baked text is larger than the maximum texture size {}",
+                size.0, size.1, max_size
+            ));
+        }
+
+        let (texture, view, target) = self
+            .factory
+            .create_render_target::<BakedForm>(size.0 as u16, size.1 as u16)
+            .map_err(|_| format!("Failed to create {}x{} baked text texture", size.0, size.1))?;
+        let depth_target = self
+            .factory
+            .create_depth_stencil_view_only::<BakedDepthForm>(size.0 as u16, size.1 as u16)
+            .map_err(|_| format!("Failed to create {}x{} depth target", size.0, size.1))?;
+        encoder.clear(&target, [0.0; 4]);
+        encoder.clear_depth(&depth_target, 1.0);
+        encoder.clear_stencil(&depth_target, 0);
+
+        let offset = Vector {
+            x: -position.x,
+            y: -position.y,
+        };
+        let sections = self
+            .sections
+            .drain(..)
+            .map(|section| section.translated(offset))
+            .collect();
+        self.sections = sections;
+        let blend = self.blend;
+        self.blend = PREMULTIPLIED_OVER;
+        let drawn = self.draw_queued(encoder, &target, &depth_target);
+        self.blend = blend;
+        drawn?;
+        // the cache now draws into the baked texture
+        self.draw_cache = None;
+
+        Ok(Some(BakedText {
+            position,
+            size,
+            texture,
+            view,
+        }))
+    }
+
+    /// Union of the screen rects of everything queued, clipped to section bounds.
+    fn queued_pixel_bounds(&self) -> Option<Rect<f32>> {
+        let mut bounds: Option<Rect<f32>> = None;
+        for section in &self.sections {
+            let glyph_rects = section.glyphs.iter().filter_map(|glyph| {
+                match self.color_glyphs.get(&(glyph.font_id, glyph.glyph.id())) {
+                    Some(color_glyph) => {
+                        Some(color_glyph_rect(glyph, color_glyph, &self.image_atlas))
+                    }
+                    None => glyph.glyph.pixel_bounding_box().map(|rect| Rect {
+                        min: point(rect.min.x as f32, rect.min.y as f32),
+                        max: point(rect.max.x as f32, rect.max.y as f32),
+                    }),
+                }
+            });
+            let rects = glyph_rects
+                .chain(section.images.iter().map(|image| image.rect))
+                .chain(section.rects.iter().map(|rect| rect.rect));
+
+            for rect in rects {
+                let clipped = Rect {
+                    min: point(
+                        rect.min.x.max(section.bounds.min.x),
+                        rect.min.y.max(section.bounds.min.y),
+                    ),
+                    max: point(
+                        rect.max.x.min(section.bounds.max.x),
+                        rect.max.y.min(section.bounds.max.y),
+                    ),
+                };
+                if clipped.min.x >= clipped.max.x || clipped.min.y >= clipped.max.y {
+                    continue;
+                }
+                bounds = Some(match bounds {
+                    Some(bounds) => union(bounds, clipped),
+                    None => clipped,
+                });
+            }
+        }
+        bounds
+    }
+}
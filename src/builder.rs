@@ -29,7 +29,13 @@ pub struct GlyphBrushBuilder<'a> {
     gpu_cache_scale_tolerance: f32,
     gpu_cache_position_tolerance: f32,
     depth_test: gfx::state::Depth,
+    depth_sort: Option<DepthSort>,
+    stencil: Option<StencilMode>,
+    blend: gfx::state::Blend,
     texture_filter_method: texture::FilterMethod,
+    gamma_correction: Option<(f32, f32)>,
+    pixel_snap: bool,
+    shaders: Option<(&'a [u8], &'a [u8])>,
 }
 
 impl<'a> GlyphBrushBuilder<'a> {
@@ -60,7 +66,13 @@ impl<'a> GlyphBrushBuilder<'a> {
             gpu_cache_scale_tolerance: 0.5,
             gpu_cache_position_tolerance: 0.1,
             depth_test: gfx::preset::depth::PASS_TEST,
+            depth_sort: None,
+            stencil: None,
+            blend: gfx::preset::blend::ALPHA,
             texture_filter_method: texture::FilterMethod::Bilinear,
+            gamma_correction: None,
+            pixel_snap: false,
+            shaders: None,
         }
     }
 }
@@ -135,6 +147,79 @@ impl<'a> GlyphBrushBuilder<'a> {
         self
     }
 
+    /// Sorts everything queued by z before drawing, e.g. so translucent text drawn with a
+    /// depth writing [`depth_test`](#method.depth_test) blends correctly whatever order it was
+    /// queued in. Glyphs, images & rects with equal z keep their queued order.
+    ///
+    /// Defaults to unsorted, drawing in queued order
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # extern crate gfx;
+    /// # extern crate gfx_glyph;
+    /// # use gfx_glyph::{DepthSort, GlyphBrushBuilder};
+    /// # fn main() {
+    /// # let some_font: &[u8] = include_bytes!("../examples/DejaVuSans.ttf");
+    /// GlyphBrushBuilder::using_font_bytes(some_font)
+    ///     .depth_test(gfx::preset::depth::LESS_EQUAL_WRITE)
+    ///     .depth_sort(DepthSort::BackToFront)
+    ///     // ...
+    /// # ;
+    /// # }
+    /// ```
+    pub fn depth_sort(mut self, order: DepthSort) -> Self {
+        self.depth_sort = Some(order);
+        self
+    }
+
+    /// Uses the stencil buffer of depth targets, which must then have a stencil format such as
+    /// `gfx::format::DepthStencil`, either to clip text to a mask or to draw text as a mask.
+    ///
+    /// Defaults to not using the stencil buffer
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # extern crate gfx;
+    /// # extern crate gfx_glyph;
+    /// # use gfx_glyph::{GlyphBrushBuilder, StencilMode};
+    /// # fn main() {
+    /// # let some_font: &[u8] = include_bytes!("../examples/DejaVuSans.ttf");
+    /// GlyphBrushBuilder::using_font_bytes(some_font)
+    ///     .stencil(StencilMode::ClipTo { reference: 1 })
+    ///     // ...
+    /// # ;
+    /// # }
+    /// ```
+    pub fn stencil(mut self, stencil: StencilMode) -> Self {
+        self.stencil = Some(stencil);
+        self
+    }
+
+    /// Sets how text is blended onto targets.
+    ///
+    /// Defaults to `gfx::preset::blend::ALPHA`
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # extern crate gfx;
+    /// # extern crate gfx_glyph;
+    /// # use gfx_glyph::GlyphBrushBuilder;
+    /// # fn main() {
+    /// # let some_font: &[u8] = include_bytes!("../examples/DejaVuSans.ttf");
+    /// GlyphBrushBuilder::using_font_bytes(some_font)
+    ///     .blend(gfx::preset::blend::ADD)
+    ///     // ...
+    /// # ;
+    /// # }
+    /// ```
+    pub fn blend(mut self, blend: gfx::state::Blend) -> Self {
+        self.blend = blend;
+        self
+    }
+
     /// Sets the texture filtering method.
     ///
     /// Defaults to `Bilinear`
@@ -157,7 +242,92 @@ impl<'a> GlyphBrushBuilder<'a> {
         self
     }
 
+    /// Enables gamma correction & contrast enhancement of glyph coverage, so text has the
+    /// same apparent weight on sRGB targets, which blend in linear space, as on other targets.
+    ///
+    /// On sRGB targets coverage is raised to a power between `1 / gamma`, for dark text, and
+    /// `gamma`, for light text. On all targets coverage is then boosted by `contrast`, where
+    /// `0.0` means no enhancement.
+    ///
+    /// Defaults to disabled
+    ///
+    /// # Example
+    /// ```no_run
+    /// # extern crate gfx;
+    /// # extern crate gfx_glyph;
+    /// # use gfx_glyph::GlyphBrushBuilder;
+    /// # fn main() {
+    /// # let some_font: &[u8] = include_bytes!("../examples/DejaVuSans.ttf");
+    /// GlyphBrushBuilder::using_font_bytes(some_font)
+    ///     .gamma_correction(1.8, 0.25)
+    ///     // ...
+    /// # ;
+    /// # }
+    /// ```
+    pub fn gamma_correction(mut self, gamma: f32, contrast: f32) -> Self {
+        self.gamma_correction = Some((gamma, contrast));
+        self
+    }
+
+    /// Moves each section after transformation so its glyphs lie on whole target pixels,
+    /// avoiding blurry text when the transform has a fractional translation. A section moves
+    /// as a whole, so its clipped quads & clip mask stay aligned. Sections can override this
+    /// with [`Section::pixel_snap`](struct.Section.html#structfield.pixel_snap).
+    ///
+    /// Defaults to `false`
+    ///
+    /// # Example
+    /// ```no_run
+    /// # extern crate gfx;
+    /// # extern crate gfx_glyph;
+    /// # use gfx_glyph::GlyphBrushBuilder;
+    /// # fn main() {
+    /// # let some_font: &[u8] = include_bytes!("../examples/DejaVuSans.ttf");
+    /// GlyphBrushBuilder::using_font_bytes(some_font)
+    ///     .pixel_snap(true)
+    ///     // ...
+    /// # ;
+    /// # }
+    /// ```
+    pub fn pixel_snap(mut self, pixel_snap: bool) -> Self {
+        self.pixel_snap = pixel_snap;
+        self
+    }
+
+    /// Sets GLSL vertex & fragment shader sources to use instead of the built-in ones.
+    ///
+    /// The shaders may use any of the built-in shader inputs & uniforms, see `src/shader`, and
+    /// additionally the uniforms `float time`, `vec4 user_vec4` & `sampler2D user_tex`, set
+    /// with [`set_time`](struct.GlyphBrush.html#method.set_time),
+    /// [`set_user_vec4`](struct.GlyphBrush.html#method.set_user_vec4) &
+    /// [`set_user_texture`](struct.GlyphBrush.html#method.set_user_texture).
+    ///
+    /// Defaults to the built-in shaders
+    ///
+    /// # Example
+    /// ```no_run
+    /// # extern crate gfx;
+    /// # extern crate gfx_glyph;
+    /// # use gfx_glyph::GlyphBrushBuilder;
+    /// # fn main() {
+    /// # let some_font: &[u8] = include_bytes!("../examples/DejaVuSans.ttf");
+    /// # let vertex_shader: &[u8] = &[];
+    /// # let fragment_shader: &[u8] = &[];
+    /// GlyphBrushBuilder::using_font_bytes(some_font)
+    ///     .shaders(vertex_shader, fragment_shader)
+    ///     // ...
+    /// # ;
+    /// # }
+    /// ```
+    pub fn shaders(mut self, vertex: &'a [u8], fragment: &'a [u8]) -> Self {
+        self.shaders = Some((vertex, fragment));
+        self
+    }
+
     /// Builds a `GlyphBrush` using the input gfx factory
+    ///
+    /// # Panics
+    /// If the shaders fail to compile or link.
     pub fn build<R, F>(self, mut factory: F) -> GlyphBrush<'a, R, F>
     where
         R: gfx::Resources,
@@ -165,14 +335,24 @@ impl<'a> GlyphBrushBuilder<'a> {
     {
         let (cache_width, cache_height) = self.initial_cache_size;
         let font_cache_tex = create_texture(&mut factory, cache_width, cache_height).unwrap();
+        let (vertex_shader, fragment_shader) = self.shaders.unwrap_or((
+            &include_bytes!("shader/vert.glsl")[..],
+            &include_bytes!("shader/frag.glsl")[..],
+        ));
         let program = factory
-            .link_program(
-                include_bytes!("shader/vert.glsl"),
-                include_bytes!("shader/frag.glsl"),
-            )
+            .link_program(vertex_shader, fragment_shader)
             .unwrap();
+        let user_tex = create_user_texture(&mut factory).unwrap();
+        let image_atlas = ImageAtlas::new(&mut factory, 64, 64).unwrap();
+        let palette = ColorTable::new(&mut factory, 16, [1.0; 4]).unwrap();
+        let tints = ColorTable::new(&mut factory, 16, [1.0; 4]).unwrap();
+        let sampler = factory.create_sampler(texture::SamplerInfo::new(
+            self.texture_filter_method,
+            texture::WrapMode::Clamp,
+        ));
 
         GlyphBrush {
+            blend: self.blend,
             sections: vec![],
             fonts: self.font_data,
             font_cache: Cache::builder()
@@ -181,13 +361,35 @@ impl<'a> GlyphBrushBuilder<'a> {
                 .position_tolerance(self.gpu_cache_position_tolerance)
                 .build(),
             font_cache_tex,
-            texture_filter_method: self.texture_filter_method,
+            image_atlas,
+            palette,
+            clip_stack: vec![],
+            color_glyphs: HashMap::new(),
+            color_bitmaps: HashMap::new(),
+            color_layers: HashMap::new(),
 
             factory,
+            pick_program: None,
+            pick_pso_cache: HashMap::new(),
             program,
+            pso_cache: HashMap::new(),
+            sampler,
+            stencil: self.stencil,
+            scale_factor: 1.0,
             draw_cache: None,
+            drawn_sections: vec![],
 
+            depth_sort: self.depth_sort,
             depth_test: self.depth_test,
+            gamma_correction: self.gamma_correction,
+            pixel_snap: self.pixel_snap,
+
+            time: 0.0,
+            tint_count: 0,
+            tints,
+            transform_stack: vec![],
+            user_tex,
+            user_vec4: [0.0; 4],
 
             #[cfg(feature = "performance_stats")]
             perf: performance_stats::PerformanceStats::default(),
@@ -0,0 +1,548 @@
+//! CBDT/CBLC & sbix colour bitmap glyphs, e.g. emoji, decoded into the RGBA image atlas as
+//! they're first drawn.
+use super::*;
+use crate::tables::{find_table, read_i16, read_u16, read_u32};
+
+/// Largest width & height of a bitmap that's decoded, larger bitmaps are rejected before
+/// their pixels are allocated.
+const MAX_BITMAP_SIZE: u32 = 1024;
+
+/// The CBDT/CBLC or sbix colour bitmaps of a font, parsed from the font data.
+///
+/// Glyphs of a font with color bitmaps set using
+/// [`GlyphBrush::set_color_bitmaps`](struct.GlyphBrush.html#method.set_color_bitmaps) are
+/// drawn as their bitmaps, like glyphs added with
+/// [`add_color_glyph`](struct.GlyphBrush.html#method.add_color_glyph). Only PNG bitmaps are
+/// supported, glyphs without one are drawn from their outlines.
+#[derive(Clone, Debug)]
+pub struct ColorBitmaps {
+    /// CBDT or sbix table data, which the bitmap PNG ranges are in
+    data: Vec<u8>,
+    /// Sorted by ppem
+    strikes: Vec<Strike>,
+}
+
+#[derive(Clone, Debug)]
+struct Strike {
+    glyphs: HashMap<u16, Bitmap>,
+    ppem: f32,
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Bitmap {
+    /// Distance from the glyph origin to the bitmap's left edge, in bitmap pixels
+    left: f32,
+    png: (usize, usize),
+    vertical: BitmapEdge,
+}
+
+/// Vertical position of a bitmap, as the distance of one of its edges above the baseline in
+/// bitmap pixels.
+#[derive(Clone, Copy, Debug)]
+enum BitmapEdge {
+    Bottom(f32),
+    Top(f32),
+}
+
+/// A colour bitmap decoded by [`ColorBitmaps::decode`].
+pub(crate) struct DecodedBitmap {
+    /// Offset of the image's top left corner from the glyph position, in image pixels
+    pub bearing: Vector<f32>,
+    /// Non-premultiplied RGBA pixels, rows top first
+    pub data: Vec<[u8; 4]>,
+    pub height: u32,
+    pub ppem: f32,
+    pub width: u32,
+}
+
+impl ColorBitmaps {
+    /// Parses the CBDT & CBLC tables, or else the sbix table, from TrueType/OpenType font
+    /// data, for font collections using the first font. Returns `None` if the font has neither.
+    pub fn parse(font_data: &[u8]) -> Option<Self> {
+        match (
+            find_table(font_data, b"CBLC"),
+            find_table(font_data, b"CBDT"),
+        ) {
+            (Some(cblc), Some(cbdt)) => parse_cbdt(cblc, cbdt),
+            _ => parse_sbix(
+                find_table(font_data, b"sbix")?,
+                find_table(font_data, b"maxp")?,
+            ),
+        }
+    }
+
+    /// Number of bitmap sizes.
+    pub fn strike_count(&self) -> usize {
+        self.strikes.len()
+    }
+
+    /// Decodes a glyph's bitmap from the smallest strike at least `ppem` pixels per em, or else
+    /// the largest. Returns `None` if the glyph has no bitmap. Glyphs whose bitmaps fail to
+    /// decode are forgotten, so they're then drawn from their outlines.
+    pub(crate) fn decode(
+        &mut self,
+        glyph: GlyphId,
+        ppem: f32,
+    ) -> Option<Result<DecodedBitmap, String>> {
+        let id = glyph.0 as u16;
+        let (strike_ppem, bitmap) = {
+            let mut strikes = self
+                .strikes
+                .iter()
+                .filter_map(|strike| Some((strike.ppem, *strike.glyphs.get(&id)?)));
+            let first = strikes.next()?;
+            let mut found = first;
+            for strike in Some(first).into_iter().chain(strikes) {
+                found = strike;
+                if strike.0 >= ppem {
+                    break;
+                }
+            }
+            found
+        };
+
+        let (data, width, height) = match decode_png(&self.data[bitmap.png.0..bitmap.png.1]) {
+            Ok(image) => image,
+            Err(err) => {
+                for strike in &mut self.strikes {
+                    strike.glyphs.remove(&id);
+                }
+                return Some(Err(err));
+            }
+        };
+        let top = match bitmap.vertical {
+            BitmapEdge::Top(top) => top,
+            BitmapEdge::Bottom(bottom) => bottom + height as f32,
+        };
+        Some(Ok(DecodedBitmap {
+            bearing: Vector {
+                x: bitmap.left,
+                y: -top,
+            },
+            data,
+            height,
+            ppem: strike_ppem,
+            width,
+        }))
+    }
+}
+
+/// Decodes a PNG image into RGBA pixels, returning them with the width & height. Fails for
+/// images larger than `MAX_BITMAP_SIZE`.
+fn decode_png(data: &[u8]) -> Result<(Vec<[u8; 4]>, u32, u32), String> {
+    let max_pixels = u64::from(MAX_BITMAP_SIZE) * u64::from(MAX_BITMAP_SIZE);
+    let mut decoder = png::Decoder::new_with_limits(data, png::Limits { pixels: max_pixels });
+    decoder.set_transformations(png::Transformations::EXPAND | png::Transformations::STRIP_16);
+    let (info, mut reader) = decoder.read_info().map_err(|err| err.to_string())?;
+    if info.width > MAX_BITMAP_SIZE || info.height > MAX_BITMAP_SIZE {
+        return Err(format!(
+            "{}x{} image is larger than {}x{}",
+            info.width, info.height, MAX_BITMAP_SIZE, MAX_BITMAP_SIZE
+        ));
+    }
+    if info.bit_depth != png::BitDepth::Eight {
+        return Err(format!("Unsupported bit depth {:?}", info.bit_depth));
+    }
+
+    let mut buffer = vec![0; info.buffer_size()];
+    reader
+        .next_frame(&mut buffer)
+        .map_err(|err| err.to_string())?;
+    let rgba = |pixel: &[u8]| match *pixel {
+        [l] => [l, l, l, 255],
+        [l, a] => [l, l, l, a],
+        [r, g, b] => [r, g, b, 255],
+        [r, g, b, a] => [r, g, b, a],
+        _ => unreachable!(),
+    };
+    let channels = match info.color_type {
+        png::ColorType::Grayscale => 1,
+        png::ColorType::GrayscaleAlpha => 2,
+        png::ColorType::RGB => 3,
+        png::ColorType::RGBA => 4,
+        png::ColorType::Indexed => return Err("Unexpanded palette image".to_string()),
+    };
+    let pixels = buffer
+        .chunks(info.line_size)
+        .flat_map(|row| {
+            row[..info.width as usize * channels]
+                .chunks(channels)
+                .map(rgba)
+        })
+        .collect();
+    Ok((pixels, info.width, info.height))
+}
+
+/// Parses the strikes of CBLC index subtables with PNG image formats.
+fn parse_cbdt(cblc: &[u8], cbdt: &[u8]) -> Option<ColorBitmaps> {
+    let size_count = read_u32(cblc, 4)? as usize;
+    let mut strikes = vec![];
+    for size in 0..size_count {
+        let record = 8 + size * 48;
+        let array_offset = read_u32(cblc, record)? as usize;
+        let subtable_count = read_u32(cblc, record + 8)? as usize;
+        let ppem = f32::from(*cblc.get(record + 45)?);
+
+        let mut glyphs = HashMap::new();
+        for subtable in 0..subtable_count {
+            let entry = array_offset + subtable * 8;
+            let first_glyph = read_u16(cblc, entry)?;
+            let last_glyph = read_u16(cblc, entry + 2)?;
+            let header = array_offset + read_u32(cblc, entry + 4)? as usize;
+            let index_format = read_u16(cblc, header)?;
+            let image_format = read_u16(cblc, header + 2)?;
+            let image_offset = read_u32(cblc, header + 4)? as usize;
+            let body = header + 8;
+            let glyph_count = usize::from(last_glyph.checked_sub(first_glyph)?) + 1;
+
+            // (glyph, image data start, image data end, metrics if not in the image data)
+            let mut images: Vec<(u16, usize, usize, Option<usize>)> = vec![];
+            match index_format {
+                1 | 3 => {
+                    let read_offset = |index: usize| match index_format {
+                        1 => read_u32(cblc, body + index * 4).map(|offset| offset as usize),
+                        _ => read_u16(cblc, body + index * 2).map(usize::from),
+                    };
+                    for index in 0..glyph_count {
+                        let (start, end) = (read_offset(index)?, read_offset(index + 1)?);
+                        if end > start {
+                            let glyph = first_glyph + index as u16;
+                            images.push((glyph, image_offset + start, image_offset + end, None));
+                        }
+                    }
+                }
+                2 => {
+                    let image_size = read_u32(cblc, body)? as usize;
+                    for index in 0..glyph_count {
+                        let start = image_offset + index * image_size;
+                        let glyph = first_glyph + index as u16;
+                        images.push((glyph, start, start + image_size, Some(body + 4)));
+                    }
+                }
+                4 => {
+                    let count = read_u32(cblc, body)? as usize;
+                    for index in 0..count {
+                        let pair = body + 4 + index * 4;
+                        let (start, end) = (read_u16(cblc, pair + 2)?, read_u16(cblc, pair + 6)?);
+                        if end > start {
+                            images.push((
+                                read_u16(cblc, pair)?,
+                                image_offset + usize::from(start),
+                                image_offset + usize::from(end),
+                                None,
+                            ));
+                        }
+                    }
+                }
+                5 => {
+                    let image_size = read_u32(cblc, body)? as usize;
+                    let count = read_u32(cblc, body + 12)? as usize;
+                    for index in 0..count {
+                        let start = image_offset + index * image_size;
+                        let glyph = read_u16(cblc, body + 16 + index * 2)?;
+                        images.push((glyph, start, start + image_size, Some(body + 4)));
+                    }
+                }
+                _ => continue,
+            }
+
+            if !(17..=19).contains(&image_format) {
+                continue;
+            }
+            // glyphs with records out of range are skipped
+            let bitmaps = images
+                .into_iter()
+                .filter_map(|(glyph, start, end, index_metrics)| {
+                    // small or big glyph metrics, then the PNG data length & data
+                    let (metrics, png_start) = match image_format {
+                        17 => (cbdt.get(start..start + 5)?, start + 9),
+                        18 => (cbdt.get(start..start + 8)?, start + 12),
+                        _ => (cblc.get(index_metrics?..index_metrics? + 8)?, start + 4),
+                    };
+                    let png_end = png_start + read_u32(cbdt, png_start - 4)? as usize;
+                    if png_end > end.min(cbdt.len()) {
+                        return None;
+                    }
+                    let bitmap = Bitmap {
+                        left: f32::from(metrics[2] as i8),
+                        png: (png_start, png_end),
+                        vertical: BitmapEdge::Top(f32::from(metrics[3] as i8)),
+                    };
+                    Some((glyph, bitmap))
+                });
+            glyphs.extend(bitmaps);
+        }
+        strikes.push(Strike { glyphs, ppem });
+    }
+
+    Some(ColorBitmaps {
+        data: cbdt.to_vec(),
+        strikes: sorted(strikes),
+    })
+}
+
+/// Parses the strikes of an sbix table, with the glyph count from the maxp table.
+fn parse_sbix(sbix: &[u8], maxp: &[u8]) -> Option<ColorBitmaps> {
+    let glyph_count = usize::from(read_u16(maxp, 4)?);
+    let strike_count = read_u32(sbix, 4)? as usize;
+    let mut strikes = vec![];
+    for strike in 0..strike_count {
+        let strike_offset = read_u32(sbix, 8 + strike * 4)? as usize;
+        let ppem = f32::from(read_u16(sbix, strike_offset)?);
+        let glyph_data = |glyph: usize| -> Option<(usize, usize)> {
+            let offsets = strike_offset + 4 + glyph * 4;
+            let start = strike_offset + read_u32(sbix, offsets)? as usize;
+            let end = strike_offset + read_u32(sbix, offsets + 4)? as usize;
+            Some((start, end))
+        };
+
+        // glyphs with records out of range are skipped
+        let bitmap = |glyph: usize| -> Option<Bitmap> {
+            let (mut start, mut end) = glyph_data(glyph)?;
+            if end <= start {
+                return None;
+            }
+            // the data of a duplicate glyph is the id of the glyph it duplicates
+            if sbix.get(start + 4..start + 8)? == b"dupe" {
+                let (dupe_start, dupe_end) = glyph_data(usize::from(read_u16(sbix, start + 8)?))?;
+                start = dupe_start;
+                end = dupe_end;
+            }
+            if end < start + 8 || sbix.get(start + 4..start + 8)? != b"png " || end > sbix.len() {
+                return None;
+            }
+            Some(Bitmap {
+                left: f32::from(read_i16(sbix, start)?),
+                png: (start + 8, end),
+                vertical: BitmapEdge::Bottom(f32::from(read_i16(sbix, start + 2)?)),
+            })
+        };
+        let glyphs = (0..glyph_count)
+            .filter_map(|glyph| Some((glyph as u16, bitmap(glyph)?)))
+            .collect();
+        strikes.push(Strike { glyphs, ppem });
+    }
+
+    Some(ColorBitmaps {
+        data: sbix.to_vec(),
+        strikes: sorted(strikes),
+    })
+}
+
+fn sorted(mut strikes: Vec<Strike>) -> Vec<Strike> {
+    strikes.sort_by(|a, b| a.ppem.partial_cmp(&b.ppem).unwrap_or(cmp::Ordering::Equal));
+    strikes
+}
+
+impl<'font, R: gfx::Resources, F: gfx::Factory<R>> GlyphBrush<'font, R, F> {
+    /// Decodes & adds the colour bitmaps of glyphs of sections not drawn before, for fonts
+    /// with colour bitmaps set.
+    pub(crate) fn cache_color_bitmaps(
+        &mut self,
+        sections: &[Section<'font>],
+    ) -> Result<(), String> {
+        for section in sections {
+            for glyph in &section.glyphs {
+                let id = glyph.glyph.id();
+                if self.color_glyphs.contains_key(&(glyph.font_id, id)) {
+                    continue;
+                }
+                let decoded = match self.color_bitmaps.get_mut(&glyph.font_id) {
+                    Some(bitmaps) => bitmaps.decode(id, pixels_per_em(glyph)),
+                    None => None,
+                };
+                match decoded {
+                    Some(Ok(DecodedBitmap {
+                        bearing,
+                        data,
+                        height,
+                        ppem,
+                        width,
+                    })) => self.add_color_glyph(
+                        glyph.font_id,
+                        id,
+                        ColorGlyph {
+                            data: &data,
+                            width,
+                            height,
+                            ppem,
+                            bearing,
+                        },
+                    )?,
+                    Some(Err(err)) => warn!(
+                        "Failed to decode the colour bitmap of font {} glyph {}: {}",
+                        glyph.font_id, id.0, err
+                    ),
+                    None => {}
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tables::test_font;
+
+    /// 8x8 RGBA image in two IDAT chunks, dynamic Huffman coded, with rows using each filter
+    /// type in turn.
+    const RGBA: &[u8] = &[
+        0x89, 0x50, 0x4e, 0x47, 0x0d, 0x0a, 0x1a, 0x0a, 0x00, 0x00, 0x00, 0x0d, 0x49, 0x48, 0x44,
+        0x52, 0x00, 0x00, 0x00, 0x08, 0x00, 0x00, 0x00, 0x08, 0x08, 0x06, 0x00, 0x00, 0x00, 0xc4,
+        0x0f, 0xbe, 0x8b, 0x00, 0x00, 0x00, 0x1e, 0x49, 0x44, 0x41, 0x54, 0x78, 0xda, 0x85, 0xce,
+        0xb1, 0x11, 0xc2, 0x30, 0x10, 0x44, 0xd1, 0x35, 0x50, 0x80, 0x4a, 0xb8, 0x12, 0x28, 0x41,
+        0x25, 0xb8, 0x04, 0x95, 0xe0, 0x12, 0x54, 0x02, 0x25, 0x5c, 0x09, 0x17, 0x25, 0x33, 0xea,
+        0x00, 0x00, 0x00, 0x6b, 0x49, 0x44, 0x41, 0x54, 0x94, 0xa0, 0x42, 0x08, 0x4c, 0x42, 0x42,
+        0x42, 0x42, 0x80, 0x2c, 0xe9, 0x58, 0x7b, 0x94, 0x10, 0x78, 0x08, 0x5e, 0xf6, 0x67, 0x67,
+        0x01, 0xc0, 0x04, 0xce, 0x3c, 0xc4, 0x02, 0xce, 0x16, 0xe1, 0x4d, 0x31, 0x5a, 0x42, 0xb0,
+        0x19, 0x93, 0x0d, 0x10, 0xb7, 0x05, 0x7b, 0x0e, 0x0c, 0xb0, 0x46, 0xd4, 0xa8, 0x52, 0xa1,
+        0x85, 0x32, 0x7d, 0x8e, 0xf0, 0x12, 0x9d, 0x73, 0xad, 0xab, 0x5d, 0xe9, 0x96, 0xd3, 0xb6,
+        0xc0, 0x29, 0x6a, 0x54, 0xa9, 0xfc, 0xd2, 0xd1, 0x44, 0x43, 0xf6, 0x3a, 0xbd, 0x83, 0xc6,
+        0x57, 0xd4, 0xcb, 0x53, 0x55, 0x1f, 0x49, 0xaf, 0xf7, 0x59, 0xd3, 0x6d, 0x40, 0x0a, 0xeb,
+        0x99, 0xbc, 0xe7, 0xef, 0xc9, 0x2f, 0xc4, 0x7a, 0x56, 0x9a, 0x17, 0xb6, 0xdc, 0x01, 0x00,
+        0x00, 0x00, 0x00, 0x49, 0x45, 0x4e, 0x44, 0xae, 0x42, 0x60, 0x82,
+    ];
+
+    /// 5x6 4 bit palette image with transparency, in a stored block followed by a fixed
+    /// Huffman coded block.
+    const PALETTE: &[u8] = &[
+        0x89, 0x50, 0x4e, 0x47, 0x0d, 0x0a, 0x1a, 0x0a, 0x00, 0x00, 0x00, 0x0d, 0x49, 0x48, 0x44,
+        0x52, 0x00, 0x00, 0x00, 0x05, 0x00, 0x00, 0x00, 0x06, 0x04, 0x03, 0x00, 0x00, 0x00, 0xf9,
+        0xd5, 0x49, 0x78, 0x00, 0x00, 0x00, 0x30, 0x50, 0x4c, 0x54, 0x45, 0x00, 0xff, 0x00, 0x11,
+        0xee, 0x05, 0x22, 0xdd, 0x0a, 0x33, 0xcc, 0x0f, 0x44, 0xbb, 0x14, 0x55, 0xaa, 0x19, 0x66,
+        0x99, 0x1e, 0x77, 0x88, 0x23, 0x88, 0x77, 0x28, 0x99, 0x66, 0x2d, 0xaa, 0x55, 0x32, 0xbb,
+        0x44, 0x37, 0xcc, 0x33, 0x3c, 0xdd, 0x22, 0x41, 0xee, 0x11, 0x46, 0xff, 0x00, 0x4b, 0xb5,
+        0xdc, 0x7d, 0xe3, 0x00, 0x00, 0x00, 0x10, 0x74, 0x52, 0x4e, 0x53, 0x00, 0x10, 0x20, 0x30,
+        0x40, 0x50, 0x60, 0x70, 0x80, 0x90, 0xa0, 0xb0, 0xc0, 0xd0, 0xe0, 0xf0, 0x54, 0xe0, 0xa8,
+        0xc8, 0x00, 0x00, 0x00, 0x25, 0x49, 0x44, 0x41, 0x54, 0x78, 0x01, 0x00, 0x08, 0x00, 0xf7,
+        0xff, 0x00, 0x01, 0x23, 0x40, 0x01, 0x56, 0x22, 0x18, 0x63, 0x0a, 0x0d, 0x0d, 0x60, 0x9e,
+        0x6d, 0xb2, 0x9d, 0x25, 0x34, 0x54, 0x92, 0x61, 0xd6, 0x9e, 0x0b, 0x00, 0x38, 0xfc, 0x06,
+        0x68, 0x2d, 0x63, 0x61, 0xd7, 0x00, 0x00, 0x00, 0x00, 0x49, 0x45, 0x4e, 0x44, 0xae, 0x42,
+        0x60, 0x82,
+    ];
+
+    /// 4096x4096 RGBA image header, with too little data for the size.
+    const OVERSIZED: &[u8] = &[
+        0x89, 0x50, 0x4e, 0x47, 0x0d, 0x0a, 0x1a, 0x0a, 0x00, 0x00, 0x00, 0x0d, 0x49, 0x48, 0x44,
+        0x52, 0x00, 0x00, 0x10, 0x00, 0x00, 0x00, 0x10, 0x00, 0x08, 0x06, 0x00, 0x00, 0x00, 0xf2,
+        0xa3, 0x24, 0x17, 0x00, 0x00, 0x00, 0x0b, 0x49, 0x44, 0x41, 0x54, 0x78, 0x9c, 0x63, 0x60,
+        0x40, 0x05, 0x00, 0x00, 0x10, 0x00, 0x01, 0x39, 0xbd, 0x8f, 0x65, 0x00, 0x00, 0x00, 0x00,
+        0x49, 0x45, 0x4e, 0x44, 0xae, 0x42, 0x60, 0x82,
+    ];
+
+    fn be16(value: i16) -> [u8; 2] {
+        (value as u16).to_be_bytes()
+    }
+
+    #[test]
+    fn decode_filtered_rgba() {
+        let (data, width, height) = decode_png(RGBA).unwrap();
+        assert_eq!((width, height), (8, 8));
+        for (index, &pixel) in data.iter().enumerate() {
+            let (x, y) = (index as u8 % 8, index as u8 / 8);
+            assert_eq!(
+                pixel,
+                [x * 32, y * 32, (x + y) * 16, 255 - x * y],
+                "({}, {})",
+                x,
+                y
+            );
+        }
+    }
+
+    #[test]
+    fn decode_transparent_palette() {
+        let (data, width, height) = decode_png(PALETTE).unwrap();
+        assert_eq!((width, height), (5, 6));
+        for (index, &pixel) in data.iter().enumerate() {
+            let entry = index as u8 % 16;
+            assert_eq!(pixel, [entry * 17, 255 - entry * 17, entry * 5, entry * 16]);
+        }
+    }
+
+    #[test]
+    fn decode_rejects_oversized() {
+        assert!(decode_png(OVERSIZED).is_err());
+    }
+
+    #[test]
+    fn cbdt_small_metrics_png() {
+        let mut cblc = vec![0, 3, 0, 0, 0, 0, 0, 1];
+        // bitmap size record, index subtable array at 56
+        let mut size = vec![0; 48];
+        size[..4].copy_from_slice(&56_u32.to_be_bytes());
+        size[8..12].copy_from_slice(&1_u32.to_be_bytes());
+        size[44] = 16;
+        size[45] = 16;
+        size[46] = 32;
+        cblc.extend(size);
+        // glyphs 1 to 2, index format 1 with image format 17 from CBDT offset 4, glyph 2's
+        // data being past the end of the table
+        cblc.extend(&[0, 1, 0, 2, 0, 0, 0, 8, 0, 1, 0, 17, 0, 0, 0, 4]);
+        cblc.extend(&0_u32.to_be_bytes());
+        cblc.extend(&(9 + RGBA.len() as u32).to_be_bytes());
+        cblc.extend(&(29 + RGBA.len() as u32).to_be_bytes());
+
+        // height, width, bearing x & y, advance, then the PNG length & data
+        let mut cbdt = vec![0, 3, 0, 0, 8, 8, 1, 7, 9];
+        cbdt.extend(&(RGBA.len() as u32).to_be_bytes());
+        cbdt.extend(RGBA);
+
+        let font = test_font(&[(b"CBDT", &cbdt), (b"CBLC", &cblc)]);
+        let mut bitmaps = ColorBitmaps::parse(&font).unwrap();
+        assert_eq!(bitmaps.strike_count(), 1);
+        assert!(bitmaps.decode(GlyphId(2), 20.0).is_none());
+
+        let decoded = bitmaps.decode(GlyphId(1), 20.0).unwrap().unwrap();
+        assert_eq!((decoded.width, decoded.height), (8, 8));
+        assert_eq!(decoded.bearing, Vector { x: 1.0, y: -7.0 });
+        assert_eq!(decoded.ppem, 16.0);
+    }
+
+    #[test]
+    fn sbix_strikes_and_dupes() {
+        let maxp = [0, 0, 0x50, 0, 0, 3];
+
+        let mut sbix = vec![0, 1, 0, 1, 0, 0, 0, 2, 0, 0, 0, 16];
+        let mut strikes = vec![];
+        for &ppem in &[64_u16, 32] {
+            // glyph 0 has no data, glyph 1 is a PNG, glyph 2 duplicates glyph 1
+            let png_end = 20 + 8 + RGBA.len() as u32;
+            let mut strike = ppem.to_be_bytes().to_vec();
+            strike.extend(&72_u16.to_be_bytes());
+            for &offset in &[20, 20, png_end, png_end + 10] {
+                strike.extend(&offset.to_be_bytes());
+            }
+            strike.extend(&be16(2));
+            strike.extend(&be16(-1));
+            strike.extend(b"png ");
+            strike.extend(RGBA);
+            strike.extend(&[0, 0, 0, 0]);
+            strike.extend(b"dupe");
+            strike.extend(&[0, 1]);
+            strikes.push(strike);
+        }
+        let second_strike = 16 + strikes[0].len() as u32;
+        sbix.extend(&second_strike.to_be_bytes());
+        for strike in strikes {
+            sbix.extend(strike);
+        }
+
+        let font = test_font(&[(b"maxp", &maxp), (b"sbix", &sbix)]);
+        let mut bitmaps = ColorBitmaps::parse(&font).unwrap();
+        assert_eq!(bitmaps.strike_count(), 2);
+        assert!(bitmaps.decode(GlyphId(0), 20.0).is_none());
+
+        let decoded = bitmaps.decode(GlyphId(2), 20.0).unwrap().unwrap();
+        assert_eq!(decoded.bearing, Vector { x: 2.0, y: -7.0 });
+        assert_eq!(decoded.ppem, 32.0);
+        assert_eq!(decoded.data, decode_png(RGBA).unwrap().0);
+
+        let decoded = bitmaps.decode(GlyphId(1), 40.0).unwrap().unwrap();
+        assert_eq!(decoded.ppem, 64.0);
+        let decoded = bitmaps.decode(GlyphId(1), 100.0).unwrap().unwrap();
+        assert_eq!(decoded.ppem, 64.0);
+    }
+}
@@ -0,0 +1,134 @@
+//! COLR/CPAL version 0 colour glyph layers, drawn as several coverage glyphs each with a
+//! palette colour.
+use super::*;
+use crate::tables::{find_table, read_u16, read_u32};
+
+/// Layer palette index meaning "use the text colour".
+const FOREGROUND: u16 = 0xFFFF;
+
+/// The COLR & CPAL tables of a colour font, parsed from the font data.
+///
+/// Glyphs of a font with color layers set using
+/// [`GlyphBrush::set_color_layers`](struct.GlyphBrush.html#method.set_color_layers) are drawn as
+/// their layers, coloured using the palette selected by
+/// [`Section::cpal_palette`](struct.Section.html#structfield.cpal_palette).
+#[derive(Clone, Debug)]
+pub struct ColorLayers {
+    /// `(glyph, first layer, layer count)`, sorted by glyph
+    base_glyphs: Vec<(u16, u16, u16)>,
+    /// `(glyph, palette entry)`
+    layers: Vec<(u16, u16)>,
+    palettes: Vec<Vec<Color>>,
+}
+
+impl ColorLayers {
+    /// Parses the COLR & CPAL tables from TrueType/OpenType font data, for font collections
+    /// using the first font. Returns `None` if the font has no valid version 0 tables.
+    pub fn parse(font_data: &[u8]) -> Option<Self> {
+        let colr = find_table(font_data, b"COLR")?;
+        let cpal = find_table(font_data, b"CPAL")?;
+
+        let base_glyph_count = read_u16(colr, 2)? as usize;
+        let base_glyphs_offset = read_u32(colr, 4)? as usize;
+        let layers_offset = read_u32(colr, 8)? as usize;
+        let layer_count = read_u16(colr, 12)? as usize;
+        let mut base_glyphs = (0..base_glyph_count)
+            .map(|index| {
+                let offset = base_glyphs_offset + index * 6;
+                Some((
+                    read_u16(colr, offset)?,
+                    read_u16(colr, offset + 2)?,
+                    read_u16(colr, offset + 4)?,
+                ))
+            })
+            .collect::<Option<Vec<_>>>()?;
+        base_glyphs.sort_by_key(|&(glyph, ..)| glyph);
+        let layers = (0..layer_count)
+            .map(|index| {
+                let offset = layers_offset + index * 4;
+                Some((read_u16(colr, offset)?, read_u16(colr, offset + 2)?))
+            })
+            .collect::<Option<Vec<_>>>()?;
+        if base_glyphs
+            .iter()
+            .any(|&(_, first, count)| first as usize + count as usize > layers.len())
+        {
+            return None;
+        }
+
+        let entry_count = read_u16(cpal, 2)? as usize;
+        let palette_count = read_u16(cpal, 4)? as usize;
+        let records_offset = read_u32(cpal, 8)? as usize;
+        let palettes = (0..palette_count)
+            .map(|palette| {
+                let first_record = read_u16(cpal, 12 + palette * 2)? as usize;
+                (first_record..first_record + entry_count)
+                    .map(|record| {
+                        let bgra = cpal.get(records_offset + record * 4..)?.get(..4)?;
+                        Some([
+                            f32::from(bgra[2]) / 255.0,
+                            f32::from(bgra[1]) / 255.0,
+                            f32::from(bgra[0]) / 255.0,
+                            f32::from(bgra[3]) / 255.0,
+                        ])
+                    })
+                    .collect()
+            })
+            .collect::<Option<Vec<_>>>()?;
+
+        Some(ColorLayers {
+            base_glyphs,
+            layers,
+            palettes,
+        })
+    }
+
+    /// Number of CPAL palettes.
+    pub fn palette_count(&self) -> usize {
+        self.palettes.len()
+    }
+
+    /// Returns the coloured layer glyphs making up a glyph, or `None` if it has no layers.
+    /// Layer glyphs come from the glyph's own font.
+    ///
+    /// Layers use the text colour's alpha, layers without a CPAL colour use the text colour &
+    /// palette index.
+    /// Out of range palettes fall back to the first palette.
+    pub(crate) fn layer_glyphs<'a, 'font: 'a>(
+        &'a self,
+        glyph: &'a LayoutGlyph<'font>,
+        palette: usize,
+    ) -> Option<impl Iterator<Item = LayoutGlyph<'font>> + 'a> {
+        let id = glyph.glyph.id().0;
+        let index = self
+            .base_glyphs
+            .binary_search_by_key(&id, |&(glyph, ..)| u32::from(glyph))
+            .ok()?;
+        let (_, first, count) = self.base_glyphs[index];
+        let font = glyph.glyph.font()?;
+        let palette = self.palettes.get(palette).or_else(|| self.palettes.first());
+
+        Some(
+            self.layers[first as usize..first as usize + count as usize]
+                .iter()
+                .map(move |&(layer_id, entry)| {
+                    let (color, palette_index) =
+                        match palette.and_then(|palette| palette.get(entry as usize)) {
+                            Some(&[r, g, b, a]) if entry != FOREGROUND => {
+                                ([r, g, b, a * glyph.color[3]], None)
+                            }
+                            _ => (glyph.color, glyph.palette_index),
+                        };
+                    LayoutGlyph {
+                        color,
+                        palette_index,
+                        glyph: font
+                            .glyph(GlyphId(u32::from(layer_id)))
+                            .scaled(glyph.glyph.scale())
+                            .positioned(glyph.glyph.position()),
+                        ..*glyph
+                    }
+                }),
+        )
+    }
+}
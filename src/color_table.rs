@@ -0,0 +1,123 @@
+//! A table of colours in a single row float texture, read by index in the shaders.
+//!
+//! Changing colours only re-uploads the texture, so vertices referring to them stay valid.
+use super::*;
+
+type TableForm = format::Rgba32F;
+type TableSurface = <TableForm as format::Formatted>::Surface;
+type TableChannel = <TableForm as format::Formatted>::Channel;
+type TableSurfaceHandle<R> = handle::Texture<R, TableSurface>;
+type TableShaderView<R> = handle::ShaderResourceView<R, [f32; 4]>;
+
+pub(crate) struct ColorTable<R: gfx::Resources> {
+    colors: Vec<Color>,
+    /// Whether `colors` changed since the last upload
+    dirty: bool,
+    /// Colour of entries not yet set
+    fill: Color,
+    texture: TableSurfaceHandle<R>,
+    view: TableShaderView<R>,
+}
+
+impl<R: gfx::Resources> ColorTable<R> {
+    /// Creates a table of `len` colours, all `fill`.
+    pub(crate) fn new(
+        factory: &mut impl gfx::Factory<R>,
+        len: usize,
+        fill: Color,
+    ) -> Result<Self, Box<dyn Error>> {
+        let (texture, view) = create_table_texture(factory, len)?;
+        Ok(ColorTable {
+            colors: vec![fill; len],
+            dirty: true,
+            fill,
+            texture,
+            view,
+        })
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.colors.len()
+    }
+
+    /// Sets a colour, growing the table if necessary.
+    pub(crate) fn set(
+        &mut self,
+        factory: &mut impl gfx::Factory<R>,
+        index: usize,
+        color: Color,
+    ) -> Result<(), String> {
+        if index >= self.colors.len() {
+            let new_len = (index + 1).next_power_of_two();
+            let (texture, view) = create_table_texture(factory, new_len)
+                .map_err(|_| format!("Failed to create {}x1 colour table texture", new_len))?;
+            self.texture = texture;
+            self.view = view;
+            self.colors.resize(new_len, self.fill);
+        }
+        self.colors[index] = color;
+        self.dirty = true;
+        Ok(())
+    }
+
+    /// Uploads the colours if they changed since the last upload.
+    pub(crate) fn upload(&mut self, encoder: &mut gfx::Encoder<R, impl gfx::CommandBuffer<R>>) {
+        if !self.dirty {
+            return;
+        }
+        self.dirty = false;
+
+        let data: Vec<[u32; 4]> = self
+            .colors
+            .iter()
+            .map(|color| {
+                [
+                    color[0].to_bits(),
+                    color[1].to_bits(),
+                    color[2].to_bits(),
+                    color[3].to_bits(),
+                ]
+            })
+            .collect();
+        let info = texture::ImageInfoCommon {
+            xoffset: 0,
+            yoffset: 0,
+            zoffset: 0,
+            width: data.len() as u16,
+            height: 1,
+            depth: 0,
+            format: (),
+            mipmap: 0,
+        };
+        encoder
+            .update_texture::<TableSurface, TableForm>(&self.texture, None, info, &data)
+            .unwrap();
+    }
+
+    pub(crate) fn view(&self) -> &TableShaderView<R> {
+        &self.view
+    }
+}
+
+fn create_table_texture<R: gfx::Resources>(
+    factory: &mut impl gfx::Factory<R>,
+    len: usize,
+) -> Result<(TableSurfaceHandle<R>, TableShaderView<R>), Box<dyn Error>> {
+    let kind = texture::Kind::D2(len as texture::Size, 1, texture::AaMode::Single);
+
+    let tex = factory.create_texture(
+        kind,
+        1 as texture::Level,
+        gfx::memory::Bind::SHADER_RESOURCE,
+        gfx::memory::Usage::Dynamic,
+        Some(<TableChannel as format::ChannelTyped>::get_channel_type()),
+    )?;
+
+    let view = factory.view_texture_as_shader_resource::<TableForm>(
+        &tex,
+        (0, 0),
+        format::Swizzle::new(),
+    )?;
+
+    Ok((tex, view))
+}
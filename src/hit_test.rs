@@ -0,0 +1,222 @@
+//! Finding the glyph under a screen position, e.g. to tell which link was clicked.
+use super::*;
+
+/// A glyph found by [`hit_test`](fn.hit_test.html).
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct GlyphHit {
+    /// Index of the section in the slice, or in queued order
+    pub section: usize,
+    /// Index of the glyph in the section's glyphs
+    pub glyph: usize,
+    /// Position across the glyph's advance, `0.0` at its left edge & `1.0` at its right, e.g.
+    /// `< 0.5` to place a caret before the glyph
+    pub advance_fraction: f32,
+    /// The glyph's [`tag`](struct.LayoutGlyph.html#structfield.tag)
+    pub tag: u32,
+}
+
+/// Finds the glyph at a screen position in pixels, the topmost if glyphs overlap.
+///
+/// Glyphs are hit within the pixel rects they're drawn in, so whitespace isn't hit.
+/// Positions outside a section's bounds or clip mask rect don't hit its glyphs. Colour glyphs
+/// & clip mask alpha are only known to the brush, use
+/// [`GlyphBrush::hit_test`](struct.GlyphBrush.html#method.hit_test) for sections with those.
+pub fn hit_test(sections: &[Section], position: Point<f32>) -> Option<GlyphHit> {
+    hit_test_rects(
+        sections,
+        position,
+        |_, glyph| pixel_rect(glyph),
+        |_, _| true,
+    )
+}
+
+/// Finds the glyph at a position, hitting glyphs within `glyph_rect` & sections where
+/// `unmasked`, after clipping to bounds.
+fn hit_test_rects<'font>(
+    sections: &[Section<'font>],
+    position: Point<f32>,
+    glyph_rect: impl Fn(&Section<'font>, &LayoutGlyph<'font>) -> Option<Rect<f32>>,
+    unmasked: impl Fn(&Section<'font>, Point<f32>) -> bool,
+) -> Option<GlyphHit> {
+    let contains = |rect: Rect<f32>| {
+        position.x >= rect.min.x
+            && position.x < rect.max.x
+            && position.y >= rect.min.y
+            && position.y < rect.max.y
+    };
+    sections
+        .iter()
+        .enumerate()
+        .rev()
+        .filter(|&(_, section)| {
+            let bounds = match section.clip_mask {
+                Some(mask) => intersection(section.bounds, mask.rect),
+                None => section.bounds,
+            };
+            contains(bounds) && unmasked(section, position)
+        })
+        .filter_map(|(section_index, section)| {
+            section
+                .glyphs
+                .iter()
+                .enumerate()
+                .rev()
+                .filter(|&(_, glyph)| glyph_rect(section, glyph).is_some_and(contains))
+                .map(|(glyph_index, glyph)| {
+                    let origin = glyph.glyph.position();
+                    let advance = glyph.glyph.unpositioned().h_metrics().advance_width;
+                    let advance_fraction = if advance > 0.0 {
+                        (position.x - origin.x) / advance
+                    } else {
+                        0.0
+                    };
+                    GlyphHit {
+                        section: section_index,
+                        glyph: glyph_index,
+                        advance_fraction,
+                        tag: glyph.tag,
+                    }
+                })
+                .next()
+        })
+        .next()
+}
+
+/// Pixel rect of a glyph drawn from the glyph texture.
+fn pixel_rect(glyph: &LayoutGlyph) -> Option<Rect<f32>> {
+    glyph.glyph.pixel_bounding_box().map(|rect| Rect {
+        min: point(rect.min.x as f32, rect.min.y as f32),
+        max: point(rect.max.x as f32, rect.max.y as f32),
+    })
+}
+
+impl<'font, R: gfx::Resources, F: gfx::Factory<R>> GlyphBrush<'font, R, F> {
+    /// Finds the glyph at a position among the sections of the last
+    /// [`draw_queued`](#method.draw_queued), see [`hit_test`](fn.hit_test.html). Section
+    /// indices are in queued order.
+    ///
+    /// Glyphs are hit within the rects they're drawn in, colour glyphs within their images &
+    /// COLR glyphs within their layers. Positions where a section's clip mask is transparent
+    /// don't hit its glyphs.
+    ///
+    /// `position` is in the same units as queued sections, so it's scaled by the
+    /// [scale factor](#method.set_scale_factor) like them. Sections drawn into a viewport are
+    /// relative to its top left.
+    pub fn hit_test(&self, position: Point<f32>) -> Option<GlyphHit> {
+        let drawn_rect =
+            |glyph: &LayoutGlyph| match self.color_glyphs.get(&(glyph.font_id, glyph.glyph.id())) {
+                Some(color_glyph) => Some(color_glyph_rect(glyph, color_glyph, &self.image_atlas)),
+                None => pixel_rect(glyph),
+            };
+        let glyph_rect = |section: &Section<'font>, glyph: &LayoutGlyph<'font>| match self
+            .color_layers
+            .get(&glyph.font_id)
+            .and_then(|layers| layers.layer_glyphs(glyph, section.cpal_palette))
+        {
+            Some(layers) => layers
+                .filter_map(|layer| drawn_rect(&layer))
+                .fold(None, |rects, rect| {
+                    Some(rects.map_or(rect, |rects| union(rects, rect)))
+                }),
+            None => drawn_rect(glyph),
+        };
+        let unmasked = |section: &Section<'font>, position: Point<f32>| match section.clip_mask {
+            Some(mask) => {
+                let x = (position.x - mask.rect.min.x) / mask.rect.width();
+                let y = (position.y - mask.rect.min.y) / mask.rect.height();
+                self.image_atlas.alpha_at(mask.image.0, x, y) > 0
+            }
+            None => true,
+        };
+
+        hit_test_rects(
+            &self.drawn_sections,
+            point(
+                position.x * self.scale_factor,
+                position.y * self.scale_factor,
+            ),
+            glyph_rect,
+            unmasked,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    lazy_static! {
+        static ref FONT: Font<'static> =
+            Font::from_bytes(&include_bytes!("../examples/DejaVuSans.ttf")[..]).unwrap();
+    }
+
+    fn section(text: &str, bounds: Rect<f32>) -> Section<'static> {
+        let glyphs = FONT
+            .layout(text, Scale::uniform(20.0), point(10.0, 30.0))
+            .enumerate()
+            .map(|(index, glyph)| LayoutGlyph {
+                color: [0.0, 0.0, 0.0, 1.0],
+                font_id: 0,
+                glyph,
+                palette_index: None,
+                tag: index as u32 * 10,
+                z: None,
+            })
+            .collect();
+        Section {
+            bounds,
+            glyphs,
+            ..Section::default()
+        }
+    }
+
+    #[test]
+    fn hits_drawn_pixel_rects() {
+        let sections = [section("A B", Section::default().bounds)];
+        let a = sections[0].glyphs[0].glyph.pixel_bounding_box().unwrap();
+        let b = sections[0].glyphs[2].glyph.pixel_bounding_box().unwrap();
+
+        let hit = hit_test(&sections, point(a.min.x as f32 + 0.5, a.max.y as f32 - 0.5)).unwrap();
+        assert_eq!((hit.section, hit.glyph, hit.tag), (0, 0, 0));
+        assert!(hit.advance_fraction > 0.0 && hit.advance_fraction < 0.5);
+
+        let hit = hit_test(&sections, point(b.max.x as f32 - 0.5, b.min.y as f32 + 0.5)).unwrap();
+        assert_eq!((hit.glyph, hit.tag), (2, 20));
+        assert!(hit.advance_fraction > 0.5 && hit.advance_fraction < 1.0);
+
+        // the space between them & above the glyph rects aren't drawn
+        let gap = (a.max.x + b.min.x) as f32 / 2.0;
+        assert_eq!(hit_test(&sections, point(gap, 25.0)), None);
+        assert_eq!(
+            hit_test(&sections, point(a.min.x as f32 + 5.0, a.min.y as f32 - 1.0)),
+            None
+        );
+    }
+
+    #[test]
+    fn topmost_section_within_bounds() {
+        let clipped = Rect {
+            min: point(0.0, 0.0),
+            max: point(15.0, 100.0),
+        };
+        let sections = [
+            section("A", Section::default().bounds),
+            section("A", clipped),
+        ];
+        let a = sections[0].glyphs[0].glyph.pixel_bounding_box().unwrap();
+        let y = a.max.y as f32 - 0.5;
+
+        assert_eq!(
+            hit_test(&sections, point(a.min.x as f32 + 0.5, y))
+                .unwrap()
+                .section,
+            1
+        );
+        assert_eq!(
+            hit_test(&sections, point(a.max.x as f32 - 0.5, y))
+                .unwrap()
+                .section,
+            0
+        );
+    }
+}
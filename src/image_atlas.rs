@@ -0,0 +1,223 @@
+//! RGBA texture atlas for images drawn alongside glyphs, e.g. colour bitmap glyphs.
+//!
+//! Images are packed into shelves & never move, so their texel rects stay valid as the atlas
+//! grows. Rects are in texels rather than normalized coordinates for the same reason, the
+//! shader normalizes using the current texture size.
+use super::*;
+
+type ImageForm = format::Srgba8;
+type ImageSurface = <ImageForm as format::Formatted>::Surface;
+type ImageChannel = <ImageForm as format::Formatted>::Channel;
+type ImageSurfaceHandle<R> = handle::Texture<R, ImageSurface>;
+type ImageShaderView<R> = handle::ShaderResourceView<R, [f32; 4]>;
+
+/// Space left between images, so bilinear sampling doesn't bleed between neighbours.
+const PADDING: u32 = 1;
+
+pub(crate) struct ImageAtlas<R: gfx::Resources> {
+    height: u32,
+    images: Vec<AtlasImage>,
+    /// Indices of images not yet uploaded to `texture`
+    pending: Vec<usize>,
+    shelves: Vec<Shelf>,
+    texture: ImageSurfaceHandle<R>,
+    view: ImageShaderView<R>,
+    width: u32,
+}
+
+struct AtlasImage {
+    data: Vec<[u8; 4]>,
+    rect: Rect<u32>,
+}
+
+struct Shelf {
+    height: u32,
+    width: u32,
+    y: u32,
+}
+
+impl<R: gfx::Resources> ImageAtlas<R> {
+    pub(crate) fn new(
+        factory: &mut impl gfx::Factory<R>,
+        width: u32,
+        height: u32,
+    ) -> Result<Self, Box<dyn Error>> {
+        let (texture, view) = create_image_texture(factory, width, height)?;
+        Ok(ImageAtlas {
+            height,
+            images: vec![],
+            pending: vec![],
+            shelves: vec![],
+            texture,
+            view,
+            width,
+        })
+    }
+
+    /// Adds an image of non-premultiplied sRGB RGBA pixels, rows top first, returning its
+    /// index. Grows the atlas if there isn't enough space, up to the maximum texture size.
+    /// Fails if `data` doesn't contain `width * height` pixels or the image doesn't fit.
+    pub(crate) fn add(
+        &mut self,
+        factory: &mut impl gfx::Factory<R>,
+        width: u32,
+        height: u32,
+        data: &[[u8; 4]],
+    ) -> Result<usize, String> {
+        if data.len() != width as usize * height as usize {
+            return Err(format!(
+                "Image data has {} pixels, expected {}x{}",
+                data.len(),
+                width,
+                height
+            ));
+        }
+
+        let position = loop {
+            if let Some(position) = self.allocate(width, height) {
+                break position;
+            }
+
+            let (new_width, new_height) = (self.width * 2, self.height * 2);
+            let max_size = factory.get_capabilities().max_texture_size;
+            if new_width as usize > max_size || new_height as usize > max_size {
+                return Err(format!(
+                    "No space for {}x{} image in {}x{} image texture of maximum size {}",
+                    width, height, self.width, self.height, max_size
+                ));
+            }
+            if log_enabled!(log::Level::Warn) {
+                warn!(
+                    "Increasing image texture size {old:?} -> {new:?}",
+                    old = (self.width, self.height),
+                    new = (new_width, new_height),
+                );
+            }
+            match create_image_texture(factory, new_width, new_height) {
+                Ok((texture, view)) => {
+                    self.texture = texture;
+                    self.view = view;
+                    self.width = new_width;
+                    self.height = new_height;
+                    // the new texture is empty
+                    self.pending = (0..self.images.len()).collect();
+                }
+                Err(_) => {
+                    return Err(format!(
+                        "Failed to create {}x{} image texture",
+                        new_width, new_height
+                    ));
+                }
+            }
+        };
+
+        self.images.push(AtlasImage {
+            data: data.to_vec(),
+            rect: Rect {
+                min: position,
+                max: point(position.x + width, position.y + height),
+            },
+        });
+        self.pending.push(self.images.len() - 1);
+        Ok(self.images.len() - 1)
+    }
+
+    /// Texel rect of an added image.
+    pub(crate) fn rect(&self, index: usize) -> Rect<f32> {
+        let rect = self.images[index].rect;
+        Rect {
+            min: point(rect.min.x as f32, rect.min.y as f32),
+            max: point(rect.max.x as f32, rect.max.y as f32),
+        }
+    }
+
+    /// Alpha of an added image at a position across it, `(0.0, 0.0)` being its top left &
+    /// `(1.0, 1.0)` its bottom right, using the nearest texel.
+    pub(crate) fn alpha_at(&self, index: usize, x: f32, y: f32) -> u8 {
+        let AtlasImage { ref data, rect } = self.images[index];
+        let texel = |fraction: f32, size: u32| {
+            ((fraction * size as f32).floor().max(0.0) as u32).min(size.saturating_sub(1))
+        };
+        let (x, y) = (texel(x, rect.width()), texel(y, rect.height()));
+        data.get((y * rect.width() + x) as usize)
+            .map_or(0, |pixel| pixel[3])
+    }
+
+    /// Uploads images added since the last upload.
+    pub(crate) fn upload(&mut self, encoder: &mut gfx::Encoder<R, impl gfx::CommandBuffer<R>>) {
+        for index in self.pending.drain(..) {
+            let AtlasImage { ref data, rect } = self.images[index];
+            let info = texture::ImageInfoCommon {
+                xoffset: rect.min.x as u16,
+                yoffset: rect.min.y as u16,
+                zoffset: 0,
+                width: rect.width() as u16,
+                height: rect.height() as u16,
+                depth: 0,
+                format: (),
+                mipmap: 0,
+            };
+            encoder
+                .update_texture::<ImageSurface, ImageForm>(&self.texture, None, info, data)
+                .unwrap();
+        }
+    }
+
+    pub(crate) fn view(&self) -> &ImageShaderView<R> {
+        &self.view
+    }
+
+    fn allocate(&mut self, width: u32, height: u32) -> Option<Point<u32>> {
+        let (padded_width, padded_height) = (width + PADDING, height + PADDING);
+        let atlas_width = self.width;
+        if let Some(shelf) = self.shelves.iter_mut().find(|shelf| {
+            shelf.height >= padded_height && shelf.width + padded_width <= atlas_width
+        }) {
+            let position = point(shelf.width, shelf.y);
+            shelf.width += padded_width;
+            return Some(position);
+        }
+
+        let y = self
+            .shelves
+            .last()
+            .map_or(0, |shelf| shelf.y + shelf.height);
+        if padded_width > self.width || y + padded_height > self.height {
+            return None;
+        }
+        self.shelves.push(Shelf {
+            height: padded_height,
+            width: padded_width,
+            y,
+        });
+        Some(point(0, y))
+    }
+}
+
+fn create_image_texture<R: gfx::Resources>(
+    factory: &mut impl gfx::Factory<R>,
+    width: u32,
+    height: u32,
+) -> Result<(ImageSurfaceHandle<R>, ImageShaderView<R>), Box<dyn Error>> {
+    let kind = texture::Kind::D2(
+        width as texture::Size,
+        height as texture::Size,
+        texture::AaMode::Single,
+    );
+
+    let tex = factory.create_texture(
+        kind,
+        1 as texture::Level,
+        gfx::memory::Bind::SHADER_RESOURCE,
+        gfx::memory::Usage::Dynamic,
+        Some(<ImageChannel as format::ChannelTyped>::get_channel_type()),
+    )?;
+
+    let view = factory.view_texture_as_shader_resource::<ImageForm>(
+        &tex,
+        (0, 0),
+        format::Swizzle::new(),
+    )?;
+
+    Ok((tex, view))
+}
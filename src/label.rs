@@ -0,0 +1,423 @@
+//! Labels anchored to positions in a 3D scene, projected on the CPU into ordinary sections.
+use super::*;
+use crate::transform::multiply_transforms;
+
+/// How a [`Label3d`](struct.Label3d.html) is sized on screen.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LabelSizing {
+    /// Keeps the section's size in pixels at the label's position, whatever its distance
+    Screen,
+    /// Sizes the section as if in the scene, each section pixel covering `units_per_pixel`
+    /// world units, so it shrinks with distance. Glyphs are rasterised at their on-screen size.
+    World { units_per_pixel: f32 },
+}
+
+/// Which way a [`Label3d`](struct.Label3d.html) faces.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LabelOrientation {
+    /// Faces the camera
+    Billboard,
+    /// Lies in a plane of the scene, e.g. a name on the ground, with section x along the
+    /// `right` & y along the `down` unit vectors. Glyphs stay upright on screen, each placed,
+    /// sized & depth tested where its origin lies on the plane.
+    Plane { right: [f32; 3], down: [f32; 3] },
+}
+
+/// A section anchored to a world position, see
+/// [`GlyphBrush::queue_label`](struct.GlyphBrush.html#method.queue_label).
+pub struct Label3d<'font> {
+    pub orientation: LabelOrientation,
+    /// World position of the section's origin
+    pub position: [f32; 3],
+    pub projection: [[f32; 4]; 4],
+    /// Laid out in pixels relative to the anchor, e.g. centred horizontally above `(0, 0)`
+    pub section: Section<'font>,
+    pub sizing: LabelSizing,
+    pub view: [[f32; 4]; 4],
+}
+
+impl<'font, R: gfx::Resources, F: gfx::Factory<R>> GlyphBrush<'font, R, F> {
+    /// Queues a label, projecting its position to screen using `screen_dimensions`, in the same
+    /// units as queued sections. The label's section z is replaced by the projected depth, so
+    /// labels can be depth tested against the scene when drawn without a transform. Labels of
+    /// any orientation can be queued together.
+    ///
+    /// Labels behind the camera aren't queued, nor are glyphs of plane labels behind it.
+    pub fn queue_label(&mut self, label: Label3d<'font>, screen_dimensions: (f32, f32)) {
+        let view_projection = multiply_transforms(label.projection, label.view);
+        let anchor = match project(view_projection, label.position, screen_dimensions) {
+            Some(anchor) => anchor,
+            None => return,
+        };
+        let view = label.view;
+        let camera_right = [view[0][0], view[1][0], view[2][0]];
+        let section = Section {
+            z: anchor.z,
+            ..label.section
+        };
+
+        match label.orientation {
+            LabelOrientation::Billboard => {
+                let factor = match label.sizing {
+                    LabelSizing::Screen => 1.0,
+                    // screen distance covered by one section pixel along the camera's right
+                    LabelSizing::World { units_per_pixel } => match projected_length(
+                        view_projection,
+                        label.position,
+                        scaled(camera_right, units_per_pixel),
+                        screen_dimensions,
+                    ) {
+                        Some(factor) => factor,
+                        None => return,
+                    },
+                };
+                let section = if factor == 1.0 {
+                    section
+                } else {
+                    section.scaled(factor)
+                };
+                self.queue_section(section.translated(Vector {
+                    x: anchor.x,
+                    y: anchor.y,
+                }));
+            }
+            LabelOrientation::Plane { right, down } => {
+                let units_per_pixel = match label.sizing {
+                    // world units covering a screen pixel at the anchor
+                    LabelSizing::Screen => match projected_length(
+                        view_projection,
+                        label.position,
+                        camera_right,
+                        screen_dimensions,
+                    ) {
+                        Some(pixels) if pixels > 0.0 => 1.0 / pixels,
+                        _ => return,
+                    },
+                    LabelSizing::World { units_per_pixel } => units_per_pixel,
+                };
+                let plane = LabelPlane {
+                    down: scaled(down, units_per_pixel),
+                    origin: label.position,
+                    right: scaled(right, units_per_pixel),
+                    screen_dimensions,
+                    view_projection,
+                };
+                self.queue_section(plane.project_section(section));
+            }
+        }
+    }
+}
+
+/// A plane label's section pixels in the scene, projected to screen.
+struct LabelPlane {
+    /// World offset of a section pixel down
+    down: [f32; 3],
+    origin: [f32; 3],
+    /// World offset of a section pixel right
+    right: [f32; 3],
+    screen_dimensions: (f32, f32),
+    view_projection: [[f32; 4]; 4],
+}
+
+impl LabelPlane {
+    /// World position of a section position.
+    fn world(&self, position: Point<f32>) -> [f32; 3] {
+        let mut world = self.origin;
+        for (axis, value) in world.iter_mut().enumerate() {
+            *value += self.right[axis] * position.x + self.down[axis] * position.y;
+        }
+        world
+    }
+
+    fn project(&self, position: Point<f32>) -> Option<Projected> {
+        project(
+            self.view_projection,
+            self.world(position),
+            self.screen_dimensions,
+        )
+    }
+
+    /// Screen rect around the projected corners of a rect, `None` if any is behind the camera.
+    /// Unbounded rects stay unbounded.
+    fn project_rect(&self, rect: Rect<f32>) -> Option<Rect<f32>> {
+        if !(rect.min.x.is_finite()
+            && rect.min.y.is_finite()
+            && rect.max.x.is_finite()
+            && rect.max.y.is_finite())
+        {
+            return Some(rect);
+        }
+        let corners = [
+            rect.min,
+            point(rect.max.x, rect.min.y),
+            point(rect.min.x, rect.max.y),
+            rect.max,
+        ];
+        let mut projected = corners.iter().map(|&corner| {
+            let corner = self.project(corner)?;
+            let corner = point(corner.x, corner.y);
+            Some(Rect {
+                min: corner,
+                max: corner,
+            })
+        });
+        let first = projected.next()??;
+        projected.try_fold(first, |rects, rect| Some(union(rects, rect?)))
+    }
+
+    /// Projects the glyphs, images & rects of a section lying in the plane, dropping any
+    /// behind the camera.
+    fn project_section<'font>(&self, section: Section<'font>) -> Section<'font> {
+        let glyphs = section
+            .glyphs
+            .into_iter()
+            .filter_map(|glyph| {
+                let position = glyph.glyph.position();
+                let projected = self.project(position)?;
+                // screen pixels covered by a section pixel where the glyph lies
+                let factor = projected_length(
+                    self.view_projection,
+                    self.world(position),
+                    self.right,
+                    self.screen_dimensions,
+                )?;
+                let scale = glyph.glyph.scale();
+                Some(LayoutGlyph {
+                    glyph: glyph
+                        .glyph
+                        .unpositioned()
+                        .unscaled()
+                        .clone()
+                        .scaled(Scale {
+                            x: scale.x * factor,
+                            y: scale.y * factor,
+                        })
+                        .positioned(point(projected.x, projected.y)),
+                    z: Some(projected.z),
+                    ..glyph
+                })
+            })
+            .collect();
+        let images = section
+            .images
+            .iter()
+            .filter_map(|image| {
+                Some(LayoutImage {
+                    rect: self.project_rect(image.rect)?,
+                    ..*image
+                })
+            })
+            .collect();
+        let rects = section
+            .rects
+            .iter()
+            .filter_map(|rect| {
+                Some(LayoutRect {
+                    rect: self.project_rect(rect.rect)?,
+                    ..*rect
+                })
+            })
+            .collect();
+        let unbounded = Section::default().bounds;
+
+        Section {
+            bounds: self.project_rect(section.bounds).unwrap_or(unbounded),
+            clip_mask: section.clip_mask.and_then(|mask| {
+                Some(ClipMask {
+                    rect: self.project_rect(mask.rect)?,
+                    ..mask
+                })
+            }),
+            glyphs,
+            gradient: section.gradient.map(|gradient| match gradient.extent {
+                GradientExtent::Rect(extent) => Gradient {
+                    extent: GradientExtent::Rect(self.project_rect(extent).unwrap_or(unbounded)),
+                    ..gradient
+                },
+                GradientExtent::Glyph => gradient,
+            }),
+            images,
+            rects,
+            ..section
+        }
+    }
+}
+
+fn scaled(vector: [f32; 3], factor: f32) -> [f32; 3] {
+    [vector[0] * factor, vector[1] * factor, vector[2] * factor]
+}
+
+/// Screen distance in pixels between the projections of `position` & `position + step`.
+fn projected_length(
+    view_projection: [[f32; 4]; 4],
+    position: [f32; 3],
+    step: [f32; 3],
+    screen_dimensions: (f32, f32),
+) -> Option<f32> {
+    let start = project(view_projection, position, screen_dimensions)?;
+    let end = [
+        position[0] + step[0],
+        position[1] + step[1],
+        position[2] + step[2],
+    ];
+    let end = project(view_projection, end, screen_dimensions)?;
+    Some((end.x - start.x).hypot(end.y - start.y))
+}
+
+/// Projects a world position to screen pixels, origin top left, & normalized depth.
+/// Returns `None` behind the camera.
+fn project(
+    view_projection: [[f32; 4]; 4],
+    position: [f32; 3],
+    (screen_width, screen_height): (f32, f32),
+) -> Option<Projected> {
+    let mut clip = [0.0; 4];
+    for (row, value) in clip.iter_mut().enumerate() {
+        *value = view_projection[0][row] * position[0]
+            + view_projection[1][row] * position[1]
+            + view_projection[2][row] * position[2]
+            + view_projection[3][row];
+    }
+    if clip[3] <= 0.0 {
+        return None;
+    }
+    Some(Projected {
+        x: (clip[0] / clip[3] + 1.0) * 0.5 * screen_width,
+        y: (1.0 - clip[1] / clip[3]) * 0.5 * screen_height,
+        z: clip[2] / clip[3],
+    })
+}
+
+#[derive(Clone, Copy)]
+struct Projected {
+    x: f32,
+    y: f32,
+    z: f32,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::TestFactory;
+
+    const FONT_DATA: &[u8] = include_bytes!("../examples/DejaVuSans.ttf");
+
+    /// Camera at the origin looking along +z, depth 0 at z 1 & 1 at z 2.
+    const PROJECTION: [[f32; 4]; 4] = [
+        [1.0, 0.0, 0.0, 0.0],
+        [0.0, 1.0, 0.0, 0.0],
+        [0.0, 0.0, 2.0, 1.0],
+        [0.0, 0.0, -2.0, 0.0],
+    ];
+    const IDENTITY: [[f32; 4]; 4] = [
+        [1.0, 0.0, 0.0, 0.0],
+        [0.0, 1.0, 0.0, 0.0],
+        [0.0, 0.0, 1.0, 0.0],
+        [0.0, 0.0, 0.0, 1.0],
+    ];
+
+    /// 'A's at 20px with origins at section pixels `xs`.
+    fn section(xs: &[f32]) -> Section<'static> {
+        let font = Font::from_bytes(FONT_DATA).unwrap();
+        let glyphs = xs
+            .iter()
+            .map(|&x| LayoutGlyph {
+                color: [0.0, 0.0, 0.0, 1.0],
+                font_id: 0,
+                glyph: font
+                    .glyph('A')
+                    .scaled(Scale::uniform(20.0))
+                    .positioned(point(x, 0.0)),
+                palette_index: None,
+                tag: 0,
+                z: None,
+            })
+            .collect();
+        Section {
+            glyphs,
+            ..Section::default()
+        }
+    }
+
+    /// Screen position, scale & z of each glyph of a queued section.
+    fn glyphs(section: &Section) -> Vec<[f32; 4]> {
+        section
+            .glyphs
+            .iter()
+            .map(|glyph| {
+                let position = glyph.glyph.position();
+                let z = glyph.z.unwrap_or(section.z);
+                [position.x, position.y, glyph.glyph.scale().y, z]
+            })
+            .collect()
+    }
+
+    fn assert_glyphs(actual: Vec<[f32; 4]>, expected: &[[f32; 4]]) {
+        assert_eq!(actual.len(), expected.len());
+        for (actual, expected) in actual.iter().zip(expected) {
+            for (actual, expected) in actual.iter().zip(expected) {
+                assert_relative_eq!(actual, expected, epsilon = 1e-3);
+            }
+        }
+    }
+
+    #[test]
+    fn billboard_and_plane_labels_queue_together() {
+        let mut brush = GlyphBrushBuilder::using_font_bytes(FONT_DATA).build(TestFactory::new());
+        let label = |orientation, xs: &[f32]| Label3d {
+            orientation,
+            position: [0.0, 0.0, 1.0],
+            projection: PROJECTION,
+            section: section(xs),
+            sizing: LabelSizing::World {
+                units_per_pixel: 0.01,
+            },
+            view: IDENTITY,
+        };
+        brush.queue_label(
+            label(LabelOrientation::Billboard, &[0.0, 100.0]),
+            (200.0, 100.0),
+        );
+        // a wall receding to the right
+        let wall = LabelOrientation::Plane {
+            right: [0.6, 0.0, 0.8],
+            down: [0.0, -1.0, 0.0],
+        };
+        brush.queue_label(label(wall, &[0.0, 100.0]), (200.0, 100.0));
+        // glyphs behind the camera are dropped
+        brush.queue_label(label(wall, &[0.0, -200.0]), (200.0, 100.0));
+
+        // a section pixel covers a screen pixel at the anchor, the billboard stays flat
+        assert_glyphs(
+            glyphs(&brush.sections[0]),
+            &[[100.0, 50.0, 20.0, 0.0], [200.0, 50.0, 20.0, 0.0]],
+        );
+        // further along the wall glyphs are nearer the centre, smaller & deeper
+        assert_glyphs(
+            glyphs(&brush.sections[1]),
+            &[[100.0, 50.0, 11.905, 0.0], [133.333, 50.0, 3.687, 0.889]],
+        );
+        assert_glyphs(glyphs(&brush.sections[2]), &[[100.0, 50.0, 11.905, 0.0]]);
+    }
+
+    #[test]
+    fn screen_sized_plane_label_keeps_its_size_at_the_anchor() {
+        let mut brush = GlyphBrushBuilder::using_font_bytes(FONT_DATA).build(TestFactory::new());
+        // on the ground at z 2, reading along +x with its top away from the camera
+        brush.queue_label(
+            Label3d {
+                orientation: LabelOrientation::Plane {
+                    right: [1.0, 0.0, 0.0],
+                    down: [0.0, 0.0, -1.0],
+                },
+                position: [0.0, -1.0, 2.0],
+                projection: PROJECTION,
+                section: section(&[0.0]),
+                sizing: LabelSizing::Screen,
+                view: IDENTITY,
+            },
+            (200.0, 100.0),
+        );
+        assert_glyphs(glyphs(&brush.sections[0]), &[[100.0, 75.0, 20.0, 1.0]]);
+    }
+}
@@ -14,17 +14,41 @@ extern crate gfx;
 #[macro_use]
 extern crate log;
 
+mod bake;
 mod builder;
+mod color_bitmaps;
+mod color_layers;
+mod color_table;
+mod hit_test;
+mod image_atlas;
+mod label;
+mod line_metrics;
 #[cfg(feature = "performance_stats")]
 mod performance_stats;
+mod picking;
 mod pipe;
+mod tables;
+#[cfg(test)]
+mod tests;
+mod transform;
 
+pub use bake::BakedText;
 pub use builder::*;
+pub use color_bitmaps::ColorBitmaps;
+pub use color_layers::ColorLayers;
+pub use hit_test::{hit_test, GlyphHit};
+pub use label::{Label3d, LabelOrientation, LabelSizing};
+pub use line_metrics::LineMetrics;
+pub use picking::{decode_pick, PickFormat, PickedGlyph};
 pub use rusttype::{
     Font, Glyph, GlyphId, HMetrics, Point, PositionedGlyph, Rect, Scale, ScaledGlyph, SharedBytes,
     VMetrics, Vector,
 };
+pub use transform::{
+    billboard, multiply_transforms, ndc_to_pixels, ortho_top_left, pixel_transform, plane,
+};
 
+use crate::{color_table::ColorTable, image_atlas::ImageAtlas};
 use gfx::{
     format, handle,
     handle::{RawDepthStencilView, RawRenderTargetView},
@@ -32,8 +56,11 @@ use gfx::{
     traits::FactoryExt,
 };
 use pipe::*;
-use rusttype::{gpu_cache::Cache, point};
-use std::error::Error;
+use rusttype::{
+    gpu_cache::{Cache, CachedBy},
+    point,
+};
+use std::{cmp, collections::HashMap, error::Error};
 
 pub(crate) type Color = [f32; 4];
 
@@ -42,6 +69,135 @@ pub struct LayoutGlyph<'font> {
     pub color: Color,
     pub font_id: usize,
     pub glyph: PositionedGlyph<'font>,
+    /// Index of a [palette](struct.GlyphBrush.html#method.set_palette) colour multiplying
+    /// `color`, changeable without regenerating vertices.
+    pub palette_index: Option<u16>,
+    /// User data, e.g. an index of the link or source text the glyph came from, returned by
+    /// [`hit_test`](fn.hit_test.html)
+    pub tag: u32,
+    /// Depth overriding [`Section::z`](struct.Section.html#structfield.z), e.g. for text on a
+    /// tilted plane drawn with [depth testing](struct.GlyphBrushBuilder.html#method.depth_test)
+    pub z: Option<f32>,
+}
+
+/// An image drawn among a section's glyphs, see [`add_image`](struct.GlyphBrush.html#method.add_image).
+#[derive(Clone, Copy, Debug)]
+pub struct LayoutImage {
+    /// Multiplies the image's colours
+    pub color: Color,
+    pub image: ImageId,
+    /// Screen rect, in pixels, the image is stretched over
+    pub rect: Rect<f32>,
+}
+
+/// A solid filled rect drawn with a section, e.g. an underline or a highlight background.
+#[derive(Clone, Copy, Debug)]
+pub struct LayoutRect {
+    pub color: Color,
+    /// Index of a [palette](struct.GlyphBrush.html#method.set_palette) colour multiplying
+    /// `color`, changeable without regenerating vertices.
+    pub palette_index: Option<u16>,
+    /// Screen rect, in pixels
+    pub rect: Rect<f32>,
+}
+
+/// Colours interpolated across a section's quads, multiplying their colours.
+#[derive(Clone, Copy, Debug)]
+pub struct Gradient {
+    /// Colours at the left top, right top, left bottom & right bottom corners of the extent
+    pub colors: [Color; 4],
+    pub extent: GradientExtent,
+}
+
+/// What a [`Gradient`](struct.Gradient.html) spans.
+#[derive(Clone, Copy, Debug)]
+pub enum GradientExtent {
+    /// Each glyph, image & rect quad
+    Glyph,
+    /// A screen rect in pixels, e.g. around a whole section. Colours are clamped outside it.
+    Rect(Rect<f32>),
+}
+
+/// Identifies an image added with [`add_image`](struct.GlyphBrush.html#method.add_image).
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct ImageId(usize);
+
+/// Order to draw glyphs, images & rects in by depth, see
+/// [`GlyphBrushBuilder::depth_sort`](struct.GlyphBrushBuilder.html#method.depth_sort).
+///
+/// Lower z values are taken to be nearer, as with the default depth range & an identity or
+/// orthographic transform.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum DepthSort {
+    /// Highest z first, so translucent text blends over whatever is behind it
+    BackToFront,
+    /// Lowest z first, so opaque text hidden by nearer text fails the depth test early
+    FrontToBack,
+}
+
+/// Stencil buffer use, see
+/// [`GlyphBrushBuilder::stencil`](struct.GlyphBrushBuilder.html#method.stencil).
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum StencilMode {
+    /// Draws text only where the stencil buffer equals `reference`, e.g. clipping it to a
+    /// rounded pane drawn into the stencil buffer beforehand
+    ClipTo { reference: u8 },
+    /// Writes `reference` into the stencil buffer wherever text has coverage, using the text
+    /// as a mask for later drawing. Text isn't drawn into the colour target
+    Mask { reference: u8 },
+}
+
+impl StencilMode {
+    pub(crate) fn reference(self) -> u8 {
+        match self {
+            StencilMode::ClipTo { reference } | StencilMode::Mask { reference } => reference,
+        }
+    }
+
+    pub(crate) fn state(self) -> gfx::state::Stencil {
+        use gfx::state::{Comparison, Stencil, StencilOp};
+        match self {
+            StencilMode::ClipTo { .. } => Stencil::new(
+                Comparison::Equal,
+                0xFF,
+                (StencilOp::Keep, StencilOp::Keep, StencilOp::Keep),
+            ),
+            StencilMode::Mask { .. } => Stencil::new(
+                Comparison::Always,
+                0xFF,
+                (StencilOp::Keep, StencilOp::Keep, StencilOp::Replace),
+            ),
+        }
+    }
+}
+
+/// An image, added with [`add_image`](struct.GlyphBrush.html#method.add_image), whose alpha
+/// clips a section, e.g. to a rounded rect or circle. See
+/// [`Section::clip_mask`](struct.Section.html#structfield.clip_mask).
+#[derive(Clone, Copy, Debug)]
+pub struct ClipMask {
+    pub image: ImageId,
+    /// Screen rect, in pixels, the mask is stretched over. Everything outside it is clipped.
+    pub rect: Rect<f32>,
+}
+
+/// Identifies a tint added with [`add_tint`](struct.GlyphBrush.html#method.add_tint).
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct TintId(usize);
+
+/// A colour bitmap glyph image, e.g. an emoji in a format
+/// [`ColorBitmaps`](struct.ColorBitmaps.html) doesn't decode, decoded by the caller. See
+/// [`add_color_glyph`](struct.GlyphBrush.html#method.add_color_glyph).
+#[derive(Clone, Copy)]
+pub struct ColorGlyph<'a> {
+    /// Non-premultiplied sRGB RGBA pixels, rows top first
+    pub data: &'a [[u8; 4]],
+    pub width: u32,
+    pub height: u32,
+    /// Pixels per em the image was drawn at, it's scaled by the glyph scale relative to this
+    pub ppem: f32,
+    /// Offset of the image's top left corner from the glyph position, in image pixels
+    pub bearing: Vector<f32>,
 }
 
 #[derive(Clone)]
@@ -49,6 +205,42 @@ pub struct Section<'font> {
     pub bounds: Rect<f32>,
     pub glyphs: Vec<LayoutGlyph<'font>>,
     pub z: f32,
+    /// CPAL palette used for glyphs of fonts with [`ColorLayers`](struct.ColorLayers.html).
+    pub cpal_palette: usize,
+    /// Images drawn after the glyphs, clipped to the same bounds
+    pub images: Vec<LayoutImage>,
+    /// Solid rects drawn before the glyphs, clipped to the same bounds
+    pub rects: Vec<LayoutRect>,
+    /// Colour gradient across the section's quads, not applied to colour glyphs
+    pub gradient: Option<Gradient>,
+    /// Tint multiplying the colours of everything in the section, e.g. to fade it
+    pub tint: Option<TintId>,
+    /// Overrides the brush's [`pixel_snap`](struct.GlyphBrushBuilder.html#method.pixel_snap)
+    /// setting, e.g. `Some(false)` to keep subpixel positions of animated text
+    pub pixel_snap: Option<bool>,
+    /// Clips the section to the alpha of a mask image, as well as to `bounds`
+    pub clip_mask: Option<ClipMask>,
+}
+
+impl Default for Section<'_> {
+    /// An empty, unbounded section.
+    fn default() -> Self {
+        Section {
+            bounds: Rect {
+                min: point(f32::NEG_INFINITY, f32::NEG_INFINITY),
+                max: point(f32::INFINITY, f32::INFINITY),
+            },
+            glyphs: vec![],
+            z: 0.0,
+            cpal_palette: 0,
+            images: vec![],
+            rects: vec![],
+            gradient: None,
+            tint: None,
+            pixel_snap: None,
+            clip_mask: None,
+        }
+    }
 }
 
 // Type for the generated glyph cache texture
@@ -71,8 +263,17 @@ const IDENTITY_MATRIX4: [[f32; 4]; 4] = [
 ///
 /// Build using a [`GlyphBrushBuilder`](struct.GlyphBrushBuilder.html).
 pub struct GlyphBrush<'font, R: gfx::Resources, F: gfx::Factory<R>> {
+    blend: gfx::state::Blend,
+    /// Current clip rects, each intersected with the one below
+    clip_stack: Vec<Rect<f32>>,
+    color_bitmaps: HashMap<usize, ColorBitmaps>,
+    color_glyphs: HashMap<(usize, GlyphId), ColorGlyphImage>,
+    color_layers: HashMap<usize, ColorLayers>,
+    depth_sort: Option<DepthSort>,
     depth_test: gfx::state::Depth,
     draw_cache: Option<DrawnGlyphBrush<R>>,
+    /// Sections of the last `draw_queued`, for hit testing
+    drawn_sections: Vec<Section<'font>>,
     factory: F,
     font_cache: Cache<'font>,
     font_cache_tex: (
@@ -80,11 +281,29 @@ pub struct GlyphBrush<'font, R: gfx::Resources, F: gfx::Factory<R>> {
         gfx_core::handle::ShaderResourceView<R, f32>,
     ),
     fonts: Vec<Font<'font>>,
+    gamma_correction: Option<(f32, f32)>,
+    image_atlas: ImageAtlas<R>,
+    palette: ColorTable<R>,
     #[cfg(feature = "performance_stats")]
     perf: performance_stats::PerformanceStats,
+    pixel_snap: bool,
+    /// Linked on the first picking draw
+    pick_program: Option<gfx::handle::Program<R>>,
+    pick_pso_cache:
+        HashMap<(gfx::format::Format, gfx::state::Depth), gfx::PipelineState<R, pick_pipe::Meta>>,
     program: gfx::handle::Program<R>,
+    pso_cache: HashMap<PsoKey, gfx::PipelineState<R, glyph_pipe::Meta>>,
+    scale_factor: f32,
+    sampler: handle::Sampler<R>,
     sections: Vec<Section<'font>>,
-    texture_filter_method: texture::FilterMethod,
+    stencil: Option<StencilMode>,
+    time: f32,
+    tint_count: usize,
+    tints: ColorTable<R>,
+    /// Current offsets, each including the one below
+    transform_stack: Vec<Vector<f32>>,
+    user_tex: handle::ShaderResourceView<R, [f32; 4]>,
+    user_vec4: [f32; 4],
 }
 
 impl<'font> LayoutGlyph<'font> {
@@ -98,11 +317,239 @@ impl<'font> LayoutGlyph<'font> {
             ..*self
         }
     }
+
+    /// Scales the glyph's size & position about the origin.
+    pub fn scaled(&self, factor: f32) -> Self {
+        let scale = self.glyph.scale();
+        let position = self.glyph.position();
+        LayoutGlyph {
+            glyph: self
+                .glyph
+                .unpositioned()
+                .unscaled()
+                .clone()
+                .scaled(Scale {
+                    x: scale.x * factor,
+                    y: scale.y * factor,
+                })
+                .positioned(point(position.x * factor, position.y * factor)),
+            ..*self
+        }
+    }
+}
+
+impl Gradient {
+    /// A gradient from `top` to `bottom`.
+    pub fn vertical(extent: GradientExtent, top: Color, bottom: Color) -> Self {
+        Gradient {
+            colors: [top, top, bottom, bottom],
+            extent,
+        }
+    }
+
+    /// A gradient from `left` to `right`.
+    pub fn horizontal(extent: GradientExtent, left: Color, right: Color) -> Self {
+        Gradient {
+            colors: [left, right, left, right],
+            extent,
+        }
+    }
+}
+
+impl LayoutImage {
+    pub fn translated(&self, offset: Vector<f32>) -> Self {
+        LayoutImage {
+            rect: Rect {
+                min: self.rect.min + offset,
+                max: self.rect.max + offset,
+            },
+            ..*self
+        }
+    }
+
+    /// Scales the image's rect about the origin.
+    pub fn scaled(&self, factor: f32) -> Self {
+        LayoutImage {
+            rect: scaled_rect(self.rect, factor),
+            ..*self
+        }
+    }
+}
+
+impl LayoutRect {
+    pub fn translated(&self, offset: Vector<f32>) -> Self {
+        LayoutRect {
+            rect: Rect {
+                min: self.rect.min + offset,
+                max: self.rect.max + offset,
+            },
+            ..*self
+        }
+    }
+
+    /// Scales the rect about the origin.
+    pub fn scaled(&self, factor: f32) -> Self {
+        LayoutRect {
+            rect: scaled_rect(self.rect, factor),
+            ..*self
+        }
+    }
+}
+
+impl<'font> Section<'font> {
+    /// Scales everything in the section about the origin.
+    fn scaled(self, factor: f32) -> Self {
+        Section {
+            bounds: scaled_rect(self.bounds, factor),
+            clip_mask: self.clip_mask.map(|mask| ClipMask {
+                rect: scaled_rect(mask.rect, factor),
+                ..mask
+            }),
+            glyphs: self
+                .glyphs
+                .iter()
+                .map(|glyph| glyph.scaled(factor))
+                .collect(),
+            gradient: self.gradient.map(|gradient| match gradient.extent {
+                GradientExtent::Rect(extent) => Gradient {
+                    extent: GradientExtent::Rect(scaled_rect(extent, factor)),
+                    ..gradient
+                },
+                GradientExtent::Glyph => gradient,
+            }),
+            images: self
+                .images
+                .iter()
+                .map(|image| image.scaled(factor))
+                .collect(),
+            rects: self.rects.iter().map(|rect| rect.scaled(factor)).collect(),
+            ..self
+        }
+    }
+
+    /// Translates everything in the section.
+    fn translated(self, offset: Vector<f32>) -> Self {
+        let translated_rect = |rect: Rect<f32>| Rect {
+            min: rect.min + offset,
+            max: rect.max + offset,
+        };
+        Section {
+            bounds: translated_rect(self.bounds),
+            clip_mask: self.clip_mask.map(|mask| ClipMask {
+                rect: translated_rect(mask.rect),
+                ..mask
+            }),
+            glyphs: self
+                .glyphs
+                .iter()
+                .map(|glyph| glyph.translated(offset))
+                .collect(),
+            gradient: self.gradient.map(|gradient| match gradient.extent {
+                GradientExtent::Rect(extent) => Gradient {
+                    extent: GradientExtent::Rect(translated_rect(extent)),
+                    ..gradient
+                },
+                GradientExtent::Glyph => gradient,
+            }),
+            images: self
+                .images
+                .iter()
+                .map(|image| image.translated(offset))
+                .collect(),
+            rects: self
+                .rects
+                .iter()
+                .map(|rect| rect.translated(offset))
+                .collect(),
+            ..self
+        }
+    }
 }
 
 impl<'font, R: gfx::Resources, F: gfx::Factory<R>> GlyphBrush<'font, R, F> {
-    pub fn queue_section(&mut self, section: Section<'font>) {
-        self.sections.push(section);
+    /// Queues a section to draw, translating it by the current
+    /// [transform](#method.push_transform), clipping it to the current
+    /// [clip rect](#method.push_clip) & scaling it by the [scale factor](#method.set_scale_factor).
+    pub fn queue_section(&mut self, mut section: Section<'font>) {
+        if let Some(&offset) = self.transform_stack.last() {
+            section = section.translated(offset);
+        }
+        if let Some(&clip) = self.clip_stack.last() {
+            section.bounds = intersection(section.bounds, clip);
+        }
+        if self.scale_factor == 1.0 {
+            self.sections.push(section);
+        } else {
+            self.sections.push(section.scaled(self.scale_factor));
+        }
+    }
+
+    /// Clips sections queued until the matching [`pop_clip`](#method.pop_clip) to a rect,
+    /// given relative to the current [transform](#method.push_transform), intersected with
+    /// the current clip rect.
+    pub fn push_clip(&mut self, clip: Rect<f32>) {
+        let offset = self
+            .transform_stack
+            .last()
+            .cloned()
+            .unwrap_or(Vector { x: 0.0, y: 0.0 });
+        let clip = Rect {
+            min: clip.min + offset,
+            max: clip.max + offset,
+        };
+        let clip = match self.clip_stack.last() {
+            Some(&current) => intersection(current, clip),
+            None => clip,
+        };
+        self.clip_stack.push(clip);
+    }
+
+    /// Restores the clip rect from before the last [`push_clip`](#method.push_clip).
+    ///
+    /// Fails, leaving sections unclipped, if there's no clip rect pushed.
+    pub fn pop_clip(&mut self) -> Result<(), String> {
+        self.clip_stack
+            .pop()
+            .map(|_| ())
+            .ok_or_else(|| "pop_clip without a matching push_clip".to_string())
+    }
+
+    /// Translates sections queued until the matching [`pop_transform`](#method.pop_transform)
+    /// by `offset`, in addition to the current transform.
+    pub fn push_transform(&mut self, offset: Vector<f32>) {
+        let offset = match self.transform_stack.last() {
+            Some(&current) => current + offset,
+            None => offset,
+        };
+        self.transform_stack.push(offset);
+    }
+
+    /// Restores the transform from before the last [`push_transform`](#method.push_transform).
+    ///
+    /// Fails, leaving sections untranslated, if there's no transform pushed.
+    pub fn pop_transform(&mut self) -> Result<(), String> {
+        self.transform_stack
+            .pop()
+            .map(|_| ())
+            .ok_or_else(|| "pop_transform without a matching push_transform".to_string())
+    }
+
+    /// Sets the number of physical pixels per logical pixel, e.g. the window's HiDPI factor.
+    /// Sections are queued in logical pixels & drawn scaled by this factor, so their glyphs
+    /// are rasterised at the physical pixel density.
+    ///
+    /// Changing the factor clears the glyph cache texture, as glyphs rasterised at the old
+    /// density won't be drawn again. Sections already queued keep the old factor.
+    pub fn set_scale_factor(&mut self, scale_factor: f32) {
+        if scale_factor != self.scale_factor {
+            self.scale_factor = scale_factor;
+            self.font_cache.clear();
+        }
+    }
+
+    /// Number of physical pixels per logical pixel, defaults to `1.0`.
+    pub fn scale_factor(&self) -> f32 {
+        self.scale_factor
     }
 
     pub fn draw_cached(&mut self, encoder: &mut gfx::Encoder<R, impl gfx::CommandBuffer<R>>) {
@@ -114,6 +561,8 @@ impl<'font, R: gfx::Resources, F: gfx::Factory<R>> GlyphBrush<'font, R, F> {
         transform: [[f32; 4]; 4],
         encoder: &mut gfx::Encoder<R, impl gfx::CommandBuffer<R>>,
     ) {
+        self.palette.upload(encoder);
+        self.tints.upload(encoder);
         if let Some(&mut DrawnGlyphBrush {
             ref pso,
             ref slice,
@@ -122,7 +571,13 @@ impl<'font, R: gfx::Resources, F: gfx::Factory<R>> GlyphBrush<'font, R, F> {
         }) = self.draw_cache.as_mut()
         {
             pipe_data.transform = transform;
-            encoder.draw(slice, &pso.1, pipe_data);
+            pipe_data.time = self.time;
+            pipe_data.user_vec4 = self.user_vec4;
+            pipe_data.user_tex.0 = self.user_tex.clone();
+            pipe_data.image_tex.0 = self.image_atlas.view().clone();
+            pipe_data.palette_tex.0 = self.palette.view().clone();
+            pipe_data.tint_tex.0 = self.tints.view().clone();
+            encoder.draw(slice, pso, pipe_data);
         }
 
         #[cfg(feature = "performance_stats")]
@@ -201,32 +656,160 @@ impl<'font, R: gfx::Resources, F: gfx::Factory<R>> GlyphBrush<'font, R, F> {
         target: &impl RawAndFormat<Raw = RawRenderTargetView<R>>,
         depth_target: &impl RawAndFormat<Raw = RawDepthStencilView<R>>,
     ) -> Result<(), String> {
+        let (target_width, target_height, ..) = target.as_raw().get_dimensions();
+        let viewport = Rect {
+            min: point(0, 0),
+            max: point(u32::from(target_width), u32::from(target_height)),
+        };
+        self.draw_queued_in_viewport(transform, viewport, encoder, target, depth_target)
+    }
+
+    /// Draws all queued sections into a rect of a render target, e.g. one pane of a split
+    /// screen, applying a position transform.
+    ///
+    /// `viewport` is in target pixels with the origin at the top left. Section positions are
+    /// relative to the viewport's top left & the transform maps to the viewport rather than the
+    /// whole target. Nothing is drawn outside the viewport.
+    ///
+    /// Fails, leaving the sections queued, if the viewport's min is beyond its max or it
+    /// isn't within the target.
+    ///
+    /// Trims the cache, see [caching behaviour](#caching-behaviour).
+    pub fn draw_queued_in_viewport(
+        &mut self,
+        transform: [[f32; 4]; 4],
+        viewport: Rect<u32>,
+        encoder: &mut gfx::Encoder<R, impl gfx::CommandBuffer<R>>,
+        target: &impl RawAndFormat<Raw = RawRenderTargetView<R>>,
+        depth_target: &impl RawAndFormat<Raw = RawDepthStencilView<R>>,
+    ) -> Result<(), String> {
+        let (target_width, target_height, ..) = target.as_raw().get_dimensions();
+        if viewport.min.x > viewport.max.x
+            || viewport.min.y > viewport.max.y
+            || viewport.max.x > u32::from(target_width)
+            || viewport.max.y > u32::from(target_height)
+        {
+            return Err(format!(
+                "Viewport {:?} isn't within the {}x{} target",
+                viewport, target_width, target_height
+            ));
+        }
+
         #[cfg(feature = "performance_stats")]
         self.perf.draw_start();
 
-        let (screen_width, screen_height, ..) = target.as_raw().get_dimensions();
-        let (screen_width, screen_height) = (u32::from(screen_width), u32::from(screen_height));
+        let sections: Vec<_> = self.sections.drain(..).collect();
+        if sections.iter().all(|section| {
+            section.glyphs.is_empty() && section.images.is_empty() && section.rects.is_empty()
+        }) {
+            self.drawn_sections = sections;
+            return Ok(());
+        }
 
-        let mut gpu_cache_rebuilt = false;
-        loop {
-            if !gpu_cache_rebuilt {
-                let mut no_text = true;
+        self.cache_glyphs(&sections, encoder)?;
+        #[cfg(feature = "performance_stats")]
+        self.perf.gpu_cache_done();
 
-                for section in &self.sections {
-                    for glyph in &section.glyphs {
-                        self.font_cache
-                            .queue_glyph(glyph.font_id, glyph.glyph.clone());
-                        no_text = false;
-                    }
-                }
+        let screen_dimensions = (viewport.width() as f32, viewport.height() as f32);
+        let verts = self.section_vertices(&sections, screen_dimensions);
+        #[cfg(feature = "performance_stats")]
+        self.perf.vertex_generation_done();
 
-                if no_text {
-                    return Ok(());
-                }
-            }
+        let vbuf = self.factory.create_vertex_buffer(&verts);
+        self.draw_cache = Some(DrawnGlyphBrush {
+            pipe_data: self.pipe_data(vbuf, transform, viewport, target, depth_target),
+            pso: self.pso_using(target.format(), depth_target.format()),
+            slice: instance_slice(verts.len()),
+        });
+        self.drawn_sections = sections;
+        self.draw_cached_with_transform(transform, encoder);
+        Ok(())
+    }
 
+    /// Moves the queued sections into a [`PreparedText`](struct.PreparedText.html), laid out
+    /// for a `screen_dimensions` sized target, that can be drawn any number of times to any
+    /// targets with [`draw_prepared`](#method.draw_prepared).
+    pub fn prepare(&mut self, screen_dimensions: (u32, u32)) -> PreparedText<'font, R> {
+        PreparedText {
+            screen_dimensions: (screen_dimensions.0 as f32, screen_dimensions.1 as f32),
+            sections: self.sections.drain(..).collect(),
+            vertices: None,
+        }
+    }
+
+    /// Draws prepared text onto a render target, applying a position transform. The text's
+    /// screen dimensions are stretched over the whole target.
+    ///
+    /// Vertices are generated on the first draw & reused while the prepared glyphs stay in
+    /// place in the glyph cache texture, e.g. across drawing other text or
+    /// [`draw_queued`](#method.draw_queued) calls that don't evict them.
+    ///
+    /// The glyph cache texture is shared, so if caching the prepared glyphs moves or evicts
+    /// glyphs of the last [`draw_queued`](#method.draw_queued) the draw cache is cleared.
+    /// [`draw_cached`](#method.draw_cached) then draws nothing until the next `draw_queued`.
+    pub fn draw_prepared(
+        &mut self,
+        prepared: &mut PreparedText<'font, R>,
+        transform: [[f32; 4]; 4],
+        encoder: &mut gfx::Encoder<R, impl gfx::CommandBuffer<R>>,
+        target: &impl RawAndFormat<Raw = RawRenderTargetView<R>>,
+        depth_target: &impl RawAndFormat<Raw = RawDepthStencilView<R>>,
+    ) -> Result<(), String> {
+        let change = self.cache_glyphs(&prepared.sections, encoder)?;
+        let draw_cache_valid = match change {
+            GlyphTextureChange::None => true,
+            GlyphTextureChange::Added => self.draw_cache.is_none() || self.drawn_glyphs_cached(),
+            GlyphTextureChange::Moved => false,
+        };
+        if !draw_cache_valid {
+            self.draw_cache = None;
+        }
+        // prepared glyphs evicted since the last draw may have been re-added elsewhere
+        if change != GlyphTextureChange::None || prepared.vertices.is_none() {
+            let verts = self.section_vertices(&prepared.sections, prepared.screen_dimensions);
+            prepared.vertices = if verts.is_empty() {
+                None
+            } else {
+                Some((self.factory.create_vertex_buffer(&verts), verts.len()))
+            };
+        }
+        let (vbuf, vertex_count) = match prepared.vertices {
+            Some((ref vbuf, vertex_count)) => (vbuf.clone(), vertex_count),
+            None => return Ok(()),
+        };
+
+        let (target_width, target_height, ..) = target.as_raw().get_dimensions();
+        let viewport = Rect {
+            min: point(0, 0),
+            max: point(u32::from(target_width), u32::from(target_height)),
+        };
+        let pipe_data = self.pipe_data(vbuf, transform, viewport, target, depth_target);
+        let pso = self.pso_using(target.format(), depth_target.format());
+        self.palette.upload(encoder);
+        self.tints.upload(encoder);
+        encoder.draw(&instance_slice(vertex_count), &pso, &pipe_data);
+        Ok(())
+    }
+
+    /// Caches the glyphs of sections in the glyph texture, growing it if necessary, & uploads
+    /// added images. Returns how the glyph texture changed, as glyphs of other sections may
+    /// have been evicted or moved.
+    fn cache_glyphs(
+        &mut self,
+        sections: &[Section<'font>],
+        encoder: &mut gfx::Encoder<R, impl gfx::CommandBuffer<R>>,
+    ) -> Result<GlyphTextureChange, String> {
+        self.cache_color_bitmaps(sections)?;
+        for (font_id, glyph) in self.texture_glyphs(sections) {
+            self.font_cache.queue_glyph(font_id, glyph);
+        }
+
+        let mut uploaded = false;
+        let mut resized = false;
+        let cached_by = loop {
             let tex = self.font_cache_tex.0.clone();
-            if let Err(err) = self.font_cache.cache_queued(|rect, tex_data| {
+            let err = match self.font_cache.cache_queued(|rect, tex_data| {
+                uploaded = true;
                 let info = texture::ImageInfoCommon {
                     xoffset: rect.min.x as u16,
                     yoffset: rect.min.y as u16,
@@ -241,148 +824,390 @@ impl<'font, R: gfx::Resources, F: gfx::Factory<R>> GlyphBrush<'font, R, F> {
                     .update_texture::<TexSurface, TexForm>(&tex, None, info, tex_data)
                     .unwrap();
             }) {
-                let (width, height) = self.font_cache.dimensions();
-                let (new_width, new_height) = (width * 2, height * 2);
-
-                if log_enabled!(log::Level::Warn) {
-                    warn!(
-                        "Increasing glyph texture size {old:?} -> {new:?}, as {reason:?}. \
-                         Consider building with `.initial_cache_size({new:?})` to avoid \
-                         resizing.",
-                        old = (width, height),
-                        new = (new_width, new_height),
-                        reason = err,
-                    );
-                }
+                Ok(cached_by) => break cached_by,
+                Err(err) => err,
+            };
 
-                match create_texture(&mut self.factory, new_width, new_height) {
-                    Ok((new_tex, tex_view)) => {
-                        self.font_cache
-                            .to_builder()
-                            .dimensions(new_width, new_height)
-                            .rebuild(&mut self.font_cache);
+            let (width, height) = self.font_cache.dimensions();
+            let (new_width, new_height) = (width * 2, height * 2);
 
-                        // queue is intact
-                        gpu_cache_rebuilt = true;
+            if log_enabled!(log::Level::Warn) {
+                warn!(
+                    "Increasing glyph texture size {old:?} -> {new:?}, as {reason:?}. \
+                     Consider building with `.initial_cache_size({new:?})` to avoid \
+                     resizing.",
+                    old = (width, height),
+                    new = (new_width, new_height),
+                    reason = err,
+                );
+            }
 
-                        if let Some(ref mut cache) = self.draw_cache {
-                            cache.texture_updated = true;
-                        }
+            match create_texture(&mut self.factory, new_width, new_height) {
+                Ok((new_tex, tex_view)) => {
+                    self.font_cache
+                        .to_builder()
+                        .dimensions(new_width, new_height)
+                        .rebuild(&mut self.font_cache);
 
-                        self.font_cache_tex.1 = tex_view;
-                        self.font_cache_tex.0 = new_tex;
-                        continue;
-                    }
-                    Err(_) => {
-                        return Err(format!(
-                            "Failed to create {}x{} glyph texture",
-                            new_width, new_height
-                        ));
+                    // queue is intact
+                    resized = true;
+
+                    if let Some(ref mut cache) = self.draw_cache {
+                        cache.pipe_data.font_tex.0 = tex_view.clone();
                     }
+
+                    self.font_cache_tex.1 = tex_view;
+                    self.font_cache_tex.0 = new_tex;
+                }
+                Err(_) => {
+                    return Err(format!(
+                        "Failed to create {}x{} glyph texture",
+                        new_width, new_height
+                    ));
                 }
             }
+        };
+        self.image_atlas.upload(encoder);
 
-            break;
-        }
-        #[cfg(feature = "performance_stats")]
-        self.perf.gpu_cache_done();
+        Ok(if resized || cached_by == CachedBy::Reordering {
+            GlyphTextureChange::Moved
+        } else if uploaded {
+            GlyphTextureChange::Added
+        } else {
+            GlyphTextureChange::None
+        })
+    }
 
-        let verts: Vec<GlyphVertex> = {
-            let mut verts = Vec::with_capacity(
-                self.sections
-                    .iter()
-                    .map(|section| section.glyphs.len())
-                    .sum::<usize>(),
-            );
-
-            for section in &self.sections {
-                verts.extend(section.glyphs.iter().filter_map(|glyph| {
-                    vertex(
-                        glyph,
-                        &self.font_cache,
-                        section.bounds,
-                        section.z,
-                        (screen_width as f32, screen_height as f32),
-                    )
-                }));
+    /// Glyphs drawn from the glyph texture for sections, resolving COLR layers & skipping
+    /// colour glyphs drawn from images.
+    fn texture_glyphs(&self, sections: &[Section<'font>]) -> Vec<(usize, PositionedGlyph<'font>)> {
+        let mut glyphs = vec![];
+        for section in sections {
+            for glyph in &section.glyphs {
+                if let Some(layers) = self
+                    .color_layers
+                    .get(&glyph.font_id)
+                    .and_then(|layers| layers.layer_glyphs(glyph, section.cpal_palette))
+                {
+                    glyphs.extend(layers.map(|layer| (layer.font_id, layer.glyph)));
+                } else if !self
+                    .color_glyphs
+                    .contains_key(&(glyph.font_id, glyph.glyph.id()))
+                {
+                    glyphs.push((glyph.font_id, glyph.glyph.clone()));
+                }
             }
+        }
+        glyphs
+    }
+
+    /// Whether the glyphs of the last drawn sections are all still in the glyph texture.
+    fn drawn_glyphs_cached(&self) -> bool {
+        self.texture_glyphs(&self.drawn_sections)
+            .iter()
+            .all(|(font_id, glyph)| self.font_cache.rect_for(*font_id, glyph).is_ok())
+    }
+
+    /// Generates the vertices of sections, sorted if a depth sort is set.
+    fn section_vertices(
+        &self,
+        sections: &[Section<'font>],
+        screen_dimensions: (f32, f32),
+    ) -> Vec<GlyphVertex> {
+        let mut verts = Vec::with_capacity(
+            sections
+                .iter()
+                .map(|section| section.glyphs.len() + section.images.len() + section.rects.len())
+                .sum::<usize>(),
+        );
 
-            verts
+        for (section_index, section) in sections.iter().enumerate() {
+            self.extend_section_vertices(&mut verts, section_index, section, screen_dimensions);
+        }
+
+        if let Some(depth_sort) = self.depth_sort {
+            // stable, so equal depths keep their queued order
+            verts.sort_by(|a, b| {
+                let order = a.left_top[2]
+                    .partial_cmp(&b.left_top[2])
+                    .unwrap_or(cmp::Ordering::Equal);
+                match depth_sort {
+                    DepthSort::BackToFront => order.reverse(),
+                    DepthSort::FrontToBack => order,
+                }
+            });
+        }
+
+        verts
+    }
+
+    /// Pipeline data drawing vertices into a viewport of a target, using the current
+    /// settings & textures.
+    fn pipe_data(
+        &self,
+        vbuf: handle::Buffer<R, GlyphVertex>,
+        transform: [[f32; 4]; 4],
+        viewport: Rect<u32>,
+        target: &impl RawAndFormat<Raw = RawRenderTargetView<R>>,
+        depth_target: &impl RawAndFormat<Raw = RawDepthStencilView<R>>,
+    ) -> glyph_pipe::Data<R> {
+        let (target_width, target_height, ..) = target.as_raw().get_dimensions();
+        let (coverage_gamma, coverage_contrast) = self.coverage_adjustment(target.format());
+        let sampler = &self.sampler;
+        glyph_pipe::Data {
+            vbuf,
+            font_tex: (self.font_cache_tex.1.clone(), sampler.clone()),
+            transform,
+            target_size: [f32::from(target_width), f32::from(target_height)],
+            viewport: [
+                viewport.min.x as f32,
+                viewport.min.y as f32,
+                viewport.width() as f32,
+                viewport.height() as f32,
+            ],
+            coverage_gamma,
+            coverage_contrast,
+            time: self.time,
+            user_vec4: self.user_vec4,
+            user_tex: (self.user_tex.clone(), sampler.clone()),
+            image_tex: (self.image_atlas.view().clone(), sampler.clone()),
+            palette_tex: (self.palette.view().clone(), sampler.clone()),
+            tint_tex: (self.tints.view().clone(), sampler.clone()),
+            out: target.as_raw().clone(),
+            out_depth: depth_target.as_raw().clone(),
+            // scissor rects have their origin at the bottom left
+            scissor: gfx::Rect {
+                x: viewport.min.x as u16,
+                y: target_height.saturating_sub(viewport.max.y as u16),
+                w: viewport.width() as u16,
+                h: viewport.height() as u16,
+            },
+        }
+    }
+
+    fn extend_section_vertices(
+        &self,
+        verts: &mut Vec<GlyphVertex>,
+        section_index: usize,
+        section: &Section<'font>,
+        screen_dimensions: (f32, f32),
+    ) {
+        let glyph_vertex = |glyph: &LayoutGlyph| {
+            let mut vertex = match self.color_glyphs.get(&(glyph.font_id, glyph.glyph.id())) {
+                Some(color_glyph) => color_glyph_vertex(
+                    glyph,
+                    color_glyph,
+                    &self.image_atlas,
+                    section,
+                    screen_dimensions,
+                ),
+                None => vertex(glyph, &self.font_cache, section, screen_dimensions),
+            }?;
+            if let Some(z) = glyph.z {
+                vertex.left_top[2] = z;
+            }
+            Some(vertex)
         };
-        self.sections.clear();
-        #[cfg(feature = "performance_stats")]
-        self.perf.vertex_generation_done();
 
-        let vbuf = self.factory.create_vertex_buffer(&verts);
+        let start = verts.len();
+        verts.extend(
+            section
+                .rects
+                .iter()
+                .filter_map(|rect| rect_vertex(rect, section, screen_dimensions)),
+        );
 
-        let draw_cache = if let Some(mut cache) = self.draw_cache.take() {
-            cache.pipe_data.vbuf = vbuf;
-            cache.pipe_data.out = target.as_raw().clone();
-            cache.pipe_data.out_depth = depth_target.as_raw().clone();
-            if cache.pso.0 != target.format() {
-                cache.pso = (
-                    target.format(),
-                    self.pso_using(target.format(), depth_target.format()),
-                );
+        for (glyph_index, glyph) in section.glyphs.iter().enumerate() {
+            let glyph_start = verts.len();
+            match self
+                .color_layers
+                .get(&glyph.font_id)
+                .and_then(|layers| layers.layer_glyphs(glyph, section.cpal_palette))
+            {
+                Some(layers) => verts.extend(layers.filter_map(|layer| glyph_vertex(&layer))),
+                None => verts.extend(glyph_vertex(glyph)),
             }
-            cache.slice.instances.as_mut().unwrap().0 = verts.len() as _;
-            if cache.texture_updated {
-                cache.pipe_data.font_tex.0 = self.font_cache_tex.1.clone();
-                cache.texture_updated = false;
+            // layers are picked as their glyph
+            for vertex in &mut verts[glyph_start..] {
+                vertex.pick = [section_index as u32 + 1, glyph_index as u32, glyph.tag];
             }
-            cache
-        } else {
-            DrawnGlyphBrush {
-                pipe_data: {
-                    let sampler = self.factory.create_sampler(texture::SamplerInfo::new(
-                        self.texture_filter_method,
-                        texture::WrapMode::Clamp,
-                    ));
-                    glyph_pipe::Data {
-                        vbuf,
-                        font_tex: (self.font_cache_tex.1.clone(), sampler),
-                        transform,
-                        out: target.as_raw().clone(),
-                        out_depth: depth_target.as_raw().clone(),
-                    }
-                },
-                pso: (
-                    target.format(),
-                    self.pso_using(target.format(), depth_target.format()),
-                ),
-                slice: gfx::Slice {
-                    base_vertex: 0,
-                    buffer: gfx::IndexBuffer::Auto,
-                    end: 4,
-                    instances: Some((verts.len() as _, 0)),
-                    start: 0,
-                },
-                texture_updated: false,
+        }
+
+        verts.extend(section.images.iter().filter_map(|image| {
+            image_vertex(image, &self.image_atlas, section, screen_dimensions)
+        }));
+
+        if let Some(mask) = section.clip_mask {
+            // map the clipped gl positions to mask image texels
+            let (screen_width, screen_height) = screen_dimensions;
+            let mask_texels = self.image_atlas.rect(mask.image.0);
+            let mask_position = |gl_x: f32, gl_y: f32| {
+                let x = (gl_x / 2.0 + 0.5) * screen_width;
+                let y = (0.5 - gl_y / 2.0) * screen_height;
+                [
+                    mask_texels.min.x
+                        + (x - mask.rect.min.x) / mask.rect.width() * mask_texels.width(),
+                    mask_texels.min.y
+                        + (y - mask.rect.min.y) / mask.rect.height() * mask_texels.height(),
+                ]
+            };
+            for vertex in &mut verts[start..] {
+                vertex.mask_left_top = mask_position(vertex.left_top[0], vertex.left_top[1]);
+                vertex.mask_right_bottom =
+                    mask_position(vertex.right_bottom[0], vertex.right_bottom[1]);
+                vertex.flags |= FLAG_MASK;
             }
-        };
+        }
 
-        self.draw_cache = Some(draw_cache);
-        self.draw_cached_with_transform(transform, encoder);
-        Ok(())
+        if section.pixel_snap.unwrap_or(self.pixel_snap) {
+            // the first glyph's origin rounded to a whole pixel, which glyph quads are
+            // positioned in whole pixels from
+            let origin = section.glyphs.first().map_or(point(0.0, 0.0), |glyph| {
+                let position = glyph.glyph.position();
+                point(position.x.round(), position.y.round())
+            });
+            let (screen_width, screen_height) = screen_dimensions;
+            let snap_origin = [
+                2.0 * (origin.x / screen_width - 0.5),
+                2.0 * (0.5 - origin.y / screen_height),
+                section.z,
+            ];
+            for vertex in &mut verts[start..] {
+                vertex.snap_origin = snap_origin;
+                vertex.flags |= FLAG_SNAP;
+            }
+        }
     }
 
     pub fn fonts(&self) -> &[Font<'font>] {
         &self.fonts
     }
 
+    /// Sets the palette colours referred to by
+    /// [`LayoutGlyph::palette_index`](struct.LayoutGlyph.html#structfield.palette_index) &
+    /// [`LayoutRect::palette_index`](struct.LayoutRect.html#structfield.palette_index).
+    /// Indices outside the palette use white.
+    ///
+    /// Recolours all such glyphs on the next draw, including
+    /// [`draw_cached`](#method.draw_cached), without regenerating vertices. E.g. to switch
+    /// between light & dark themes.
+    pub fn set_palette(&mut self, palette: &[Color]) -> Result<(), String> {
+        for index in 0..palette.len().max(self.palette.len()) {
+            let color = palette.get(index).cloned().unwrap_or([1.0; 4]);
+            self.palette.set(&mut self.factory, index, color)?;
+        }
+        Ok(())
+    }
+
+    /// Adds a tint, initially `color`, for use as
+    /// [`Section::tint`](struct.Section.html#structfield.tint).
+    pub fn add_tint(&mut self, color: Color) -> Result<TintId, String> {
+        // index 0 is reserved for untinted sections
+        let index = self.tint_count + 1;
+        self.tints.set(&mut self.factory, index, color)?;
+        self.tint_count = index;
+        Ok(TintId(index))
+    }
+
+    /// Changes a tint's colour, multiplying the colours of all sections using it on the next
+    /// draw, including [`draw_cached`](#method.draw_cached), without regenerating vertices.
+    /// E.g. use `[1.0, 1.0, 1.0, opacity]` to fade sections in or out.
+    pub fn set_tint(&mut self, tint: TintId, color: Color) -> Result<(), String> {
+        self.tints.set(&mut self.factory, tint.0, color)
+    }
+
+    /// Sets the `time` uniform available to [custom shaders](struct.GlyphBrushBuilder.html#method.shaders).
+    ///
+    /// Takes effect on the next draw, including [`draw_cached`](#method.draw_cached).
+    pub fn set_time(&mut self, time: f32) {
+        self.time = time;
+    }
+
+    /// Sets the `user_vec4` uniform available to
+    /// [custom shaders](struct.GlyphBrushBuilder.html#method.shaders).
+    ///
+    /// Takes effect on the next draw, including [`draw_cached`](#method.draw_cached).
+    pub fn set_user_vec4(&mut self, user_vec4: [f32; 4]) {
+        self.user_vec4 = user_vec4;
+    }
+
+    /// Sets the `user_tex` texture available to
+    /// [custom shaders](struct.GlyphBrushBuilder.html#method.shaders), sampled using the
+    /// brush's texture filter method. Defaults to a single white texel.
+    ///
+    /// Takes effect on the next draw, including [`draw_cached`](#method.draw_cached).
+    pub fn set_user_texture(&mut self, user_tex: handle::ShaderResourceView<R, [f32; 4]>) {
+        self.user_tex = user_tex;
+    }
+
+    /// Returns the `(gamma, contrast)` coverage adjustment for drawing onto a target of the
+    /// given format, `(1.0, 0.0)` meaning unadjusted.
+    fn coverage_adjustment(&self, color_format: gfx::format::Format) -> (f32, f32) {
+        match self.gamma_correction {
+            Some((gamma, contrast)) if color_format.1 == format::ChannelType::Srgb => {
+                (gamma, contrast)
+            }
+            Some((_, contrast)) => (1.0, contrast),
+            None => (1.0, 0.0),
+        }
+    }
+
+    /// Returns the pipeline state for the current settings & target formats, creating it
+    /// the first time they're used together.
     fn pso_using(
         &mut self,
         color_format: gfx::format::Format,
         depth_format: gfx::format::Format,
     ) -> gfx::PipelineState<R, glyph_pipe::Meta> {
-        self.factory
-            .create_pipeline_from_program(
-                &self.program,
-                gfx::Primitive::TriangleStrip,
-                gfx::state::Rasterizer::new_fill(),
-                glyph_pipe::Init::new(color_format, depth_format, self.depth_test),
-            )
-            .unwrap()
+        let key = PsoKey {
+            blend: self.blend,
+            color_format,
+            depth_format,
+            depth_test: self.depth_test,
+            stencil: self.stencil,
+        };
+        let factory = &mut self.factory;
+        let program = &self.program;
+        self.pso_cache
+            .entry(key)
+            .or_insert_with(|| {
+                factory
+                    .create_pipeline_from_program(
+                        program,
+                        gfx::Primitive::TriangleStrip,
+                        gfx::state::Rasterizer::new_fill(),
+                        glyph_pipe::Init::new(
+                            color_format,
+                            depth_format,
+                            key.depth_test,
+                            key.stencil,
+                            key.blend,
+                        ),
+                    )
+                    .unwrap()
+            })
+            .clone()
+    }
+
+    /// Sets the depth test, see
+    /// [`GlyphBrushBuilder::depth_test`](struct.GlyphBrushBuilder.html#method.depth_test).
+    /// Takes effect on the next [`draw_queued`](#method.draw_queued).
+    pub fn set_depth_test(&mut self, depth_test: gfx::state::Depth) {
+        self.depth_test = depth_test;
+    }
+
+    /// Sets the stencil buffer use, see
+    /// [`GlyphBrushBuilder::stencil`](struct.GlyphBrushBuilder.html#method.stencil), `None`
+    /// not using the stencil buffer. Takes effect on the next [`draw_queued`](#method.draw_queued).
+    pub fn set_stencil(&mut self, stencil: Option<StencilMode>) {
+        self.stencil = stencil;
+    }
+
+    /// Sets the blending of text onto targets, see
+    /// [`GlyphBrushBuilder::blend`](struct.GlyphBrushBuilder.html#method.blend).
+    /// Takes effect on the next [`draw_queued`](#method.draw_queued).
+    pub fn set_blend(&mut self, blend: gfx::state::Blend) {
+        self.blend = blend;
     }
 
     /// Adds an additional font to the one(s) initially added on build.
@@ -423,23 +1248,318 @@ impl<'font, R: gfx::Resources, F: gfx::Factory<R>> GlyphBrush<'font, R, F> {
     pub fn add_font<'a: 'font>(&mut self, font_data: Font<'a>) {
         self.fonts.push(font_data);
     }
+
+    /// Sets the COLR/CPAL colour layers of a font, drawing its colour glyphs as several
+    /// coloured coverage glyphs.
+    pub fn set_color_layers(&mut self, font_id: usize, color_layers: ColorLayers) {
+        self.color_layers.insert(font_id, color_layers);
+    }
+
+    /// Sets the CBDT/CBLC or sbix colour bitmaps of a font, drawing its glyphs that have a
+    /// bitmap as colour images. Bitmaps are decoded as glyphs are first drawn.
+    pub fn set_color_bitmaps(&mut self, font_id: usize, color_bitmaps: ColorBitmaps) {
+        self.color_bitmaps.insert(font_id, color_bitmaps);
+    }
+
+    /// Registers a colour image to draw in place of a font's glyph, e.g. an emoji bitmap
+    /// in a format [`ColorBitmaps`](struct.ColorBitmaps.html) doesn't decode. Such glyphs are drawn from a separate RGBA
+    /// texture & aren't tinted by [`LayoutGlyph::color`](struct.LayoutGlyph.html#structfield.color).
+    ///
+    /// Registering the same glyph again replaces its image. Fails if the image data doesn't
+    /// contain `width * height` pixels, or the texture can't grow to fit it.
+    pub fn add_color_glyph(
+        &mut self,
+        font_id: usize,
+        glyph_id: GlyphId,
+        color_glyph: ColorGlyph,
+    ) -> Result<(), String> {
+        let image = self.add_image(color_glyph.width, color_glyph.height, color_glyph.data)?;
+        self.color_glyphs.insert(
+            (font_id, glyph_id),
+            ColorGlyphImage {
+                bearing: color_glyph.bearing,
+                image,
+                ppem: color_glyph.ppem,
+            },
+        );
+        Ok(())
+    }
+
+    /// Adds an image of non-premultiplied sRGB RGBA pixels, rows top first, to the RGBA texture
+    /// shared with colour glyphs. It can then be drawn among glyphs using
+    /// [`Section::images`](struct.Section.html#structfield.images).
+    ///
+    /// Fails if `data` doesn't contain `width * height` pixels, or the texture can't grow to
+    /// fit it.
+    pub fn add_image(
+        &mut self,
+        width: u32,
+        height: u32,
+        data: &[[u8; 4]],
+    ) -> Result<ImageId, String> {
+        self.image_atlas
+            .add(&mut self.factory, width, height, data)
+            .map(ImageId)
+    }
+}
+
+struct ColorGlyphImage {
+    bearing: Vector<f32>,
+    image: ImageId,
+    ppem: f32,
+}
+
+/// Everything a pipeline state object depends on.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+struct PsoKey {
+    blend: gfx::state::Blend,
+    color_format: gfx::format::Format,
+    depth_format: gfx::format::Format,
+    depth_test: gfx::state::Depth,
+    stencil: Option<StencilMode>,
+}
+
+/// How caching glyphs changed the glyph texture.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum GlyphTextureChange {
+    None,
+    /// Glyphs were added, possibly evicting glyphs not being cached
+    Added,
+    /// Cached glyphs moved, as the texture was reordered or resized
+    Moved,
 }
 
 struct DrawnGlyphBrush<R: gfx::Resources> {
     pipe_data: glyph_pipe::Data<R>,
-    pso: (gfx::format::Format, gfx::PipelineState<R, glyph_pipe::Meta>),
+    pso: gfx::PipelineState<R, glyph_pipe::Meta>,
     slice: gfx::Slice<R>,
-    texture_updated: bool,
+}
+
+/// Sections moved out of the queue by [`GlyphBrush::prepare`](struct.GlyphBrush.html#method.prepare),
+/// to draw to several targets with
+/// [`GlyphBrush::draw_prepared`](struct.GlyphBrush.html#method.draw_prepared).
+pub struct PreparedText<'font, R: gfx::Resources> {
+    screen_dimensions: (f32, f32),
+    sections: Vec<Section<'font>>,
+    /// Vertex buffer & vertex count, `None` until first drawn or if nothing is visible
+    vertices: Option<(handle::Buffer<R, GlyphVertex>, usize)>,
+}
+
+/// Slice drawing each instance's quad as a triangle strip.
+fn instance_slice<R: gfx::Resources>(instances: usize) -> gfx::Slice<R> {
+    gfx::Slice {
+        base_vertex: 0,
+        buffer: gfx::IndexBuffer::Auto,
+        end: 4,
+        instances: Some((instances as _, 0)),
+        start: 0,
+    }
 }
 
 #[inline]
 fn vertex(
     glyph: &LayoutGlyph,
     cache: &Cache,
-    bounds: Rect<f32>,
-    z: f32,
+    section: &Section,
+    screen_dimensions: (f32, f32),
+) -> Option<GlyphVertex> {
+    let rect = cache.rect_for(glyph.font_id, &glyph.glyph);
+    if let Ok(Some((uv_rect, screen_rect))) = rect {
+        let screen_rect = Rect {
+            min: point(screen_rect.min.x as f32, screen_rect.min.y as f32),
+            max: point(screen_rect.max.x as f32, screen_rect.max.y as f32),
+        };
+        clipped_vertex(
+            screen_rect,
+            uv_rect,
+            section,
+            quad_colors(glyph.color, section.gradient.as_ref(), screen_rect),
+            glyph.palette_index,
+            0,
+            screen_dimensions,
+        )
+    } else {
+        if rect.is_err() {
+            panic!("Cache miss?: {:?}", rect);
+        }
+        None
+    }
+}
+
+#[inline]
+fn color_glyph_vertex(
+    glyph: &LayoutGlyph,
+    color_glyph: &ColorGlyphImage,
+    image_atlas: &ImageAtlas<impl gfx::Resources>,
+    section: &Section,
+    screen_dimensions: (f32, f32),
+) -> Option<GlyphVertex> {
+    clipped_vertex(
+        color_glyph_rect(glyph, color_glyph, image_atlas),
+        image_atlas.rect(color_glyph.image.0),
+        section,
+        [[1.0; 4]; 4],
+        None,
+        FLAG_COLOR_IMAGE,
+        screen_dimensions,
+    )
+}
+
+/// Em size of a glyph in pixels, its scale being the pixel height from descent to ascent.
+#[inline]
+fn pixels_per_em(glyph: &LayoutGlyph) -> f32 {
+    let scale = glyph.glyph.scale().y;
+    match glyph.glyph.font() {
+        Some(font) => {
+            let v_metrics = font.v_metrics_unscaled();
+            let height = v_metrics.ascent - v_metrics.descent;
+            if height > 0.0 {
+                scale * f32::from(font.units_per_em()) / height
+            } else {
+                scale
+            }
+        }
+        None => scale,
+    }
+}
+
+/// Screen rect of a glyph drawn as a colour image.
+#[inline]
+fn color_glyph_rect(
+    glyph: &LayoutGlyph,
+    color_glyph: &ColorGlyphImage,
+    image_atlas: &ImageAtlas<impl gfx::Resources>,
+) -> Rect<f32> {
+    let uv_rect = image_atlas.rect(color_glyph.image.0);
+    let scale = pixels_per_em(glyph) / color_glyph.ppem;
+    let min = glyph.glyph.position() + color_glyph.bearing * scale;
+    Rect {
+        min,
+        max: point(
+            min.x + uv_rect.width() * scale,
+            min.y + uv_rect.height() * scale,
+        ),
+    }
+}
+
+#[inline]
+fn image_vertex(
+    image: &LayoutImage,
+    image_atlas: &ImageAtlas<impl gfx::Resources>,
+    section: &Section,
+    screen_dimensions: (f32, f32),
+) -> Option<GlyphVertex> {
+    clipped_vertex(
+        image.rect,
+        image_atlas.rect(image.image.0),
+        section,
+        quad_colors(image.color, section.gradient.as_ref(), image.rect),
+        None,
+        FLAG_COLOR_IMAGE,
+        screen_dimensions,
+    )
+}
+
+#[inline]
+fn rect_vertex(
+    rect: &LayoutRect,
+    section: &Section,
+    screen_dimensions: (f32, f32),
+) -> Option<GlyphVertex> {
+    clipped_vertex(
+        rect.rect,
+        Rect {
+            min: point(0.0, 0.0),
+            max: point(0.0, 0.0),
+        },
+        section,
+        quad_colors(rect.color, section.gradient.as_ref(), rect.rect),
+        rect.palette_index,
+        FLAG_SOLID,
+        screen_dimensions,
+    )
+}
+
+/// Colours at the left top, right top, left bottom & right bottom corners of a quad covering
+/// a rect in screen pixels.
+#[inline]
+fn quad_colors(color: Color, gradient: Option<&Gradient>, screen_rect: Rect<f32>) -> [Color; 4] {
+    match gradient {
+        None => [color; 4],
+        Some(Gradient {
+            colors,
+            extent: GradientExtent::Glyph,
+        }) => {
+            let mut quad_colors = *colors;
+            for quad_color in &mut quad_colors {
+                *quad_color = multiply(*quad_color, color);
+            }
+            quad_colors
+        }
+        Some(Gradient {
+            colors,
+            extent: GradientExtent::Rect(extent),
+        }) => {
+            let fraction_x = |x: f32| ((x - extent.min.x) / extent.width()).clamp(0.0, 1.0);
+            let fraction_y = |y: f32| ((y - extent.min.y) / extent.height()).clamp(0.0, 1.0);
+            let (left, right) = (fraction_x(screen_rect.min.x), fraction_x(screen_rect.max.x));
+            let (top, bottom) = (fraction_y(screen_rect.min.y), fraction_y(screen_rect.max.y));
+            [
+                multiply(bilinear(colors, left, top), color),
+                multiply(bilinear(colors, right, top), color),
+                multiply(bilinear(colors, left, bottom), color),
+                multiply(bilinear(colors, right, bottom), color),
+            ]
+        }
+    }
+}
+
+/// Interpolates left top, right top, left bottom & right bottom corner colours.
+#[inline]
+fn bilinear(colors: &[Color; 4], fraction_x: f32, fraction_y: f32) -> Color {
+    let mut color = [0.0; 4];
+    for (channel, value) in color.iter_mut().enumerate() {
+        let top = colors[0][channel] + (colors[1][channel] - colors[0][channel]) * fraction_x;
+        let bottom = colors[2][channel] + (colors[3][channel] - colors[2][channel]) * fraction_x;
+        *value = top + (bottom - top) * fraction_y;
+    }
+    color
+}
+
+#[inline]
+fn multiply(a: Color, b: Color) -> Color {
+    [a[0] * b[0], a[1] * b[1], a[2] * b[2], a[3] * b[3]]
+}
+
+/// Generates the vertex of a quad covering a rect in screen pixels, clipping it to the section
+/// bounds. `colors` are at the rect's left top, right top, left bottom & right bottom corners.
+#[inline]
+fn clipped_vertex(
+    screen_rect: Rect<f32>,
+    mut uv_rect: Rect<f32>,
+    section: &Section,
+    colors: [Color; 4],
+    palette_index: Option<u16>,
+    mut flags: u32,
     (screen_width, screen_height): (f32, f32),
 ) -> Option<GlyphVertex> {
+    let bounds = match section.clip_mask {
+        Some(mask) => intersection(section.bounds, mask.rect),
+        None => section.bounds,
+    };
+    if bounds.min.x > bounds.max.x || bounds.min.y > bounds.max.y {
+        // mask doesn't overlap the bounds
+        return None;
+    }
+    if screen_rect.min.x > bounds.max.x
+        || screen_rect.min.y > bounds.max.y
+        || bounds.min.x > screen_rect.max.x
+        || bounds.min.y > screen_rect.max.y
+    {
+        // glyph is totally outside the bounds
+        return None;
+    }
+
     let gl_bounds = Rect {
         min: point(
             2.0 * (bounds.min.x / screen_width - 0.5),
@@ -451,64 +1571,104 @@ fn vertex(
         ),
     };
 
-    let rect = cache.rect_for(glyph.font_id, &glyph.glyph);
-    if let Ok(Some((mut uv_rect, screen_rect))) = rect {
-        if screen_rect.min.x as f32 > bounds.max.x
-            || screen_rect.min.y as f32 > bounds.max.y
-            || bounds.min.x > screen_rect.max.x as f32
-            || bounds.min.y > screen_rect.max.y as f32
-        {
-            // glyph is totally outside the bounds
-            return None;
-        }
+    let mut gl_rect = Rect {
+        min: point(
+            2.0 * (screen_rect.min.x / screen_width - 0.5),
+            2.0 * (0.5 - screen_rect.min.y / screen_height),
+        ),
+        max: point(
+            2.0 * (screen_rect.max.x / screen_width - 0.5),
+            2.0 * (0.5 - screen_rect.max.y / screen_height),
+        ),
+    };
 
-        let mut gl_rect = Rect {
-            min: point(
-                2.0 * (screen_rect.min.x as f32 / screen_width - 0.5),
-                2.0 * (0.5 - screen_rect.min.y as f32 / screen_height),
-            ),
-            max: point(
-                2.0 * (screen_rect.max.x as f32 / screen_width - 0.5),
-                2.0 * (0.5 - screen_rect.max.y as f32 / screen_height),
-            ),
+    let quad = gl_rect;
+
+    // handle overlapping bounds, modify uv_rect to preserve texture aspect
+    if gl_rect.max.x > gl_bounds.max.x {
+        let old_width = gl_rect.width();
+        gl_rect.max.x = gl_bounds.max.x;
+        uv_rect.max.x = uv_rect.min.x + uv_rect.width() * gl_rect.width() / old_width;
+    }
+    if gl_rect.min.x < gl_bounds.min.x {
+        let old_width = gl_rect.width();
+        gl_rect.min.x = gl_bounds.min.x;
+        uv_rect.min.x = uv_rect.max.x - uv_rect.width() * gl_rect.width() / old_width;
+    }
+    // note: y access is flipped gl compared with screen,
+    // texture is not flipped (ie is a headache)
+    if gl_rect.max.y < gl_bounds.max.y {
+        let old_height = gl_rect.height();
+        gl_rect.max.y = gl_bounds.max.y;
+        uv_rect.max.y = uv_rect.min.y + uv_rect.height() * gl_rect.height() / old_height;
+    }
+    if gl_rect.min.y > gl_bounds.min.y {
+        let old_height = gl_rect.height();
+        gl_rect.min.y = gl_bounds.min.y;
+        uv_rect.min.y = uv_rect.max.y - uv_rect.height() * gl_rect.height() / old_height;
+    }
+
+    // interpolate colours at the corners of the clipped quad
+    let color_at = |x: f32, y: f32| {
+        let fraction_x = if quad.width() != 0.0 {
+            (x - quad.min.x) / quad.width()
+        } else {
+            0.0
+        };
+        let fraction_y = if quad.height() != 0.0 {
+            (y - quad.min.y) / quad.height()
+        } else {
+            0.0
         };
+        bilinear(&colors, fraction_x, fraction_y)
+    };
 
-        // handle overlapping bounds, modify uv_rect to preserve texture aspect
-        if gl_rect.max.x > gl_bounds.max.x {
-            let old_width = gl_rect.width();
-            gl_rect.max.x = gl_bounds.max.x;
-            uv_rect.max.x = uv_rect.min.x + uv_rect.width() * gl_rect.width() / old_width;
-        }
-        if gl_rect.min.x < gl_bounds.min.x {
-            let old_width = gl_rect.width();
-            gl_rect.min.x = gl_bounds.min.x;
-            uv_rect.min.x = uv_rect.max.x - uv_rect.width() * gl_rect.width() / old_width;
-        }
-        // note: y access is flipped gl compared with screen,
-        // texture is not flipped (ie is a headache)
-        if gl_rect.max.y < gl_bounds.max.y {
-            let old_height = gl_rect.height();
-            gl_rect.max.y = gl_bounds.max.y;
-            uv_rect.max.y = uv_rect.min.y + uv_rect.height() * gl_rect.height() / old_height;
-        }
-        if gl_rect.min.y > gl_bounds.min.y {
-            let old_height = gl_rect.height();
-            gl_rect.min.y = gl_bounds.min.y;
-            uv_rect.min.y = uv_rect.max.y - uv_rect.height() * gl_rect.height() / old_height;
-        }
-
-        Some(GlyphVertex {
-            left_top: [gl_rect.min.x, gl_rect.max.y, z],
-            right_bottom: [gl_rect.max.x, gl_rect.min.y],
-            tex_left_top: [uv_rect.min.x, uv_rect.max.y],
-            tex_right_bottom: [uv_rect.max.x, uv_rect.min.y],
-            color: glyph.color,
-        })
-    } else {
-        if rect.is_err() {
-            panic!("Cache miss?: {:?}", rect);
-        }
-        None
+    if palette_index.is_some() {
+        flags |= FLAG_PALETTE;
+    }
+
+    Some(GlyphVertex {
+        left_top: [gl_rect.min.x, gl_rect.max.y, section.z],
+        right_bottom: [gl_rect.max.x, gl_rect.min.y],
+        tex_left_top: [uv_rect.min.x, uv_rect.max.y],
+        tex_right_bottom: [uv_rect.max.x, uv_rect.min.y],
+        mask_left_top: [0.0; 2],
+        mask_right_bottom: [0.0; 2],
+        color_left_top: color_at(gl_rect.min.x, gl_rect.max.y),
+        color_right_top: color_at(gl_rect.max.x, gl_rect.max.y),
+        color_left_bottom: color_at(gl_rect.min.x, gl_rect.min.y),
+        color_right_bottom: color_at(gl_rect.max.x, gl_rect.min.y),
+        flags,
+        palette_index: palette_index.map_or(0, u32::from),
+        tint_index: section.tint.map_or(0, |tint| tint.0 as u32),
+        pick: [0; 3],
+        snap_origin: [0.0; 3],
+    })
+}
+
+/// Overlap of two rects, with `min` beyond `max` if they don't overlap.
+#[inline]
+fn intersection(a: Rect<f32>, b: Rect<f32>) -> Rect<f32> {
+    Rect {
+        min: point(a.min.x.max(b.min.x), a.min.y.max(b.min.y)),
+        max: point(a.max.x.min(b.max.x), a.max.y.min(b.max.y)),
+    }
+}
+
+#[inline]
+fn union(a: Rect<f32>, b: Rect<f32>) -> Rect<f32> {
+    Rect {
+        min: point(a.min.x.min(b.min.x), a.min.y.min(b.min.y)),
+        max: point(a.max.x.max(b.max.x), a.max.y.max(b.max.y)),
+    }
+}
+
+/// Scales a rect about the origin.
+#[inline]
+fn scaled_rect(rect: Rect<f32>, factor: f32) -> Rect<f32> {
+    Rect {
+        min: point(rect.min.x * factor, rect.min.y * factor),
+        max: point(rect.max.x * factor, rect.max.y * factor),
     }
 }
 
@@ -517,7 +1677,7 @@ fn create_texture<R: gfx::Resources>(
     factory: &mut impl gfx::Factory<R>,
     width: u32,
     height: u32,
-) -> Result<(TexSurfaceHandle<R>, TexShaderView<R>), Box<Error>> {
+) -> Result<(TexSurfaceHandle<R>, TexShaderView<R>), Box<dyn Error>> {
     let kind = texture::Kind::D2(
         width as texture::Size,
         height as texture::Size,
@@ -537,3 +1697,16 @@ fn create_texture<R: gfx::Resources>(
 
     Ok((tex, view))
 }
+
+// Creates the default 1x1 white user texture
+fn create_user_texture<R: gfx::Resources>(
+    factory: &mut impl gfx::Factory<R>,
+) -> Result<handle::ShaderResourceView<R, [f32; 4]>, Box<dyn Error>> {
+    let kind = texture::Kind::D2(1, 1, texture::AaMode::Single);
+    let (_, view) = factory.create_texture_immutable::<format::Rgba8>(
+        kind,
+        texture::Mipmap::Provided,
+        &[&[[255; 4]]],
+    )?;
+    Ok(view)
+}
@@ -0,0 +1,136 @@
+//! Underline & strikethrough placement from font metrics.
+use super::*;
+use crate::tables::{find_table, read_i16, read_u16};
+
+/// Underline & strikethrough metrics, in font units, read from a font's `post` & `OS/2`
+/// tables. Used to build [`LayoutRect`](struct.LayoutRect.html)s decorating text.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct LineMetrics {
+    /// `hhea` ascent, which with the descent is the height a rusttype `Scale` is relative to
+    pub ascent: f32,
+    pub descent: f32,
+    pub units_per_em: f32,
+    /// Distance of the top of the underline above the baseline, usually negative
+    pub underline_position: f32,
+    pub underline_thickness: f32,
+    /// Distance of the top of the strikethrough above the baseline
+    pub strikeout_position: f32,
+    pub strikeout_thickness: f32,
+}
+
+impl LineMetrics {
+    /// Parses the metrics from TrueType/OpenType font data, for font collections using the
+    /// first font. Returns `None` if the `head` or `post` table is missing. Without an `OS/2`
+    /// table the strikethrough is placed at a quarter em, as thick as the underline. Without
+    /// an `hhea` table the ascent is taken to be an em.
+    pub fn parse(font_data: &[u8]) -> Option<Self> {
+        let head = find_table(font_data, b"head")?;
+        let post = find_table(font_data, b"post")?;
+        let units_per_em = f32::from(read_u16(head, 18)?);
+        let (ascent, descent) = find_table(font_data, b"hhea")
+            .and_then(|hhea| Some((f32::from(read_i16(hhea, 4)?), f32::from(read_i16(hhea, 6)?))))
+            .unwrap_or((units_per_em, 0.0));
+        let underline_position = f32::from(read_i16(post, 8)?);
+        let underline_thickness = f32::from(read_i16(post, 10)?);
+        let (strikeout_thickness, strikeout_position) = find_table(font_data, b"OS/2")
+            .and_then(|os2| Some((f32::from(read_i16(os2, 26)?), f32::from(read_i16(os2, 28)?))))
+            .unwrap_or((underline_thickness, units_per_em / 4.0));
+
+        Some(LineMetrics {
+            ascent,
+            descent,
+            units_per_em,
+            underline_position,
+            underline_thickness,
+            strikeout_position,
+            strikeout_thickness,
+        })
+    }
+
+    /// Screen rect of an underline for text at `scale` on the baseline from `start` to `end_x`.
+    pub fn underline(&self, scale: Scale, start: Point<f32>, end_x: f32) -> Rect<f32> {
+        self.line(
+            scale,
+            start,
+            end_x,
+            self.underline_position,
+            self.underline_thickness,
+        )
+    }
+
+    /// Screen rect of a strikethrough for text at `scale` on the baseline from `start` to
+    /// `end_x`.
+    pub fn strikethrough(&self, scale: Scale, start: Point<f32>, end_x: f32) -> Rect<f32> {
+        self.line(
+            scale,
+            start,
+            end_x,
+            self.strikeout_position,
+            self.strikeout_thickness,
+        )
+    }
+
+    fn line(
+        &self,
+        scale: Scale,
+        start: Point<f32>,
+        end_x: f32,
+        position: f32,
+        thickness: f32,
+    ) -> Rect<f32> {
+        // like rusttype, scale is the height from descent to ascent rather than an em
+        let height = self.ascent - self.descent;
+        let units_to_pixels = if height > 0.0 {
+            scale.y / height
+        } else {
+            scale.y / self.units_per_em
+        };
+        // at least one pixel thick, so thin lines don't vanish at small sizes
+        let thickness = (thickness * units_to_pixels).max(1.0);
+        let top = start.y - position * units_to_pixels;
+        Rect {
+            min: point(start.x, top),
+            max: point(end_x, top + thickness),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FONT_DATA: &[u8] = include_bytes!("../examples/DejaVuSans.ttf");
+
+    #[test]
+    fn lines_scale_like_glyphs() {
+        let metrics = LineMetrics::parse(FONT_DATA).unwrap();
+        assert_eq!(
+            metrics,
+            LineMetrics {
+                ascent: 1901.0,
+                descent: -483.0,
+                units_per_em: 2048.0,
+                underline_position: -40.0,
+                underline_thickness: 90.0,
+                strikeout_position: 530.0,
+                strikeout_thickness: 102.0,
+            }
+        );
+
+        // a tenth of a pixel per unit, as the font is 2384 units from descent to ascent
+        let scale = Scale::uniform(238.4);
+        let underline = metrics.underline(scale, point(10.0, 100.0), 50.0);
+        assert_relative_eq!(underline.min.y, 104.0, epsilon = 1e-3);
+        assert_relative_eq!(underline.max.y, 113.0, epsilon = 1e-3);
+        assert_eq!((underline.min.x, underline.max.x), (10.0, 50.0));
+
+        let strikethrough = metrics.strikethrough(scale, point(10.0, 100.0), 50.0);
+        assert_relative_eq!(strikethrough.min.y, 47.0, epsilon = 1e-3);
+        assert_relative_eq!(strikethrough.max.y, 57.2, epsilon = 1e-3);
+
+        // glyphs at the scale span the same height
+        let font = Font::from_bytes(FONT_DATA).unwrap();
+        let v_metrics = font.v_metrics(scale);
+        assert_relative_eq!(v_metrics.ascent - v_metrics.descent, 238.4, epsilon = 1e-3);
+    }
+}
@@ -0,0 +1,129 @@
+//! Drawing glyph ids into an integer target on the GPU, to find the glyph under a pixel
+//! whatever the transform, e.g. text in a 3D scene.
+use super::*;
+
+/// Format of picking targets, see
+/// [`GlyphBrush::draw_cached_picking`](struct.GlyphBrush.html#method.draw_cached_picking).
+pub type PickFormat = [u32; 4];
+
+/// A glyph decoded from a picking target pixel by [`decode_pick`](fn.decode_pick.html).
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct PickedGlyph {
+    /// Index of the section in queued order
+    pub section: usize,
+    /// Index of the glyph in the section's glyphs
+    pub glyph: usize,
+    /// The glyph's [`tag`](struct.LayoutGlyph.html#structfield.tag)
+    pub tag: u32,
+}
+
+/// Decodes a pixel read back from a picking target, `None` where no glyph was drawn.
+///
+/// # Example
+///
+/// ```
+/// # use gfx_glyph::*;
+/// assert_eq!(decode_pick([0, 0, 0, 0]), None);
+/// assert_eq!(
+///     decode_pick([3, 7, 42, 0]),
+///     Some(PickedGlyph {
+///         section: 2,
+///         glyph: 7,
+///         tag: 42,
+///     })
+/// );
+/// ```
+pub fn decode_pick(pixel: PickFormat) -> Option<PickedGlyph> {
+    // sections are offset by 1 so a cleared pixel is no glyph
+    if pixel[0] == 0 {
+        return None;
+    }
+    Some(PickedGlyph {
+        section: pixel[0] as usize - 1,
+        glyph: pixel[1] as usize,
+        tag: pixel[2],
+    })
+}
+
+impl<'font, R: gfx::Resources, F: gfx::Factory<R>> GlyphBrush<'font, R, F> {
+    /// Draws the glyphs of the last [`draw_queued`](#method.draw_queued) into a picking
+    /// target, reusing its vertices, so each pixel at least half covered by a glyph holds its
+    /// section & glyph index. Decode read back pixels with [`decode_pick`](fn.decode_pick.html).
+    ///
+    /// The target should be cleared to `[0; 4]` & be the size of the last drawn target, with
+    /// `transform` being the one it was drawn with. Glyphs are depth tested like the last draw.
+    /// Images & rects aren't drawn. Custom [shaders](struct.GlyphBrushBuilder.html#method.shaders)
+    /// aren't used, the picking draw always uses the built-in vertex shader.
+    pub fn draw_cached_picking(
+        &mut self,
+        transform: [[f32; 4]; 4],
+        encoder: &mut gfx::Encoder<R, impl gfx::CommandBuffer<R>>,
+        target: &handle::RenderTargetView<R, PickFormat>,
+        depth_target: &impl RawAndFormat<Raw = RawDepthStencilView<R>>,
+    ) -> Result<(), String> {
+        let (vbuf, slice, target_size, viewport, scissor) = match self.draw_cache {
+            Some(ref cache) => (
+                cache.pipe_data.vbuf.clone(),
+                cache.slice.clone(),
+                cache.pipe_data.target_size,
+                cache.pipe_data.viewport,
+                cache.pipe_data.scissor,
+            ),
+            None => return Ok(()),
+        };
+        let pso = self.pick_pso_using(depth_target.format())?;
+
+        self.palette.upload(encoder);
+        self.tints.upload(encoder);
+        let sampler = &self.sampler;
+        let pipe_data = pick_pipe::Data {
+            vbuf,
+            font_tex: (self.font_cache_tex.1.clone(), sampler.clone()),
+            transform,
+            target_size,
+            viewport,
+            image_tex: (self.image_atlas.view().clone(), sampler.clone()),
+            palette_tex: (self.palette.view().clone(), sampler.clone()),
+            tint_tex: (self.tints.view().clone(), sampler.clone()),
+            out: target.clone(),
+            out_depth: depth_target.as_raw().clone(),
+            scissor,
+        };
+        encoder.draw(&slice, &pso, &pipe_data);
+        Ok(())
+    }
+
+    /// Returns the picking pipeline state for the current depth test & a depth format,
+    /// linking the picking shaders on first use.
+    fn pick_pso_using(
+        &mut self,
+        depth_format: gfx::format::Format,
+    ) -> Result<gfx::PipelineState<R, pick_pipe::Meta>, String> {
+        if self.pick_program.is_none() {
+            let program = self
+                .factory
+                .link_program(
+                    include_bytes!("shader/vert.glsl"),
+                    include_bytes!("shader/pick_frag.glsl"),
+                )
+                .map_err(|err| format!("Failed to link picking shaders: {}", err))?;
+            self.pick_program = Some(program);
+        }
+
+        let key = (depth_format, self.depth_test);
+        if let Some(pso) = self.pick_pso_cache.get(&key) {
+            return Ok(pso.clone());
+        }
+        let pso = self
+            .factory
+            .create_pipeline_from_program(
+                self.pick_program.as_ref().unwrap(),
+                gfx::Primitive::TriangleStrip,
+                gfx::state::Rasterizer::new_fill(),
+                pick_pipe::Init::new(depth_format, self.depth_test),
+            )
+            .map_err(|err| format!("Failed to create picking pipeline: {}", err))?;
+        self.pick_pso_cache.insert(key, pso.clone());
+        Ok(pso)
+    }
+}
@@ -6,19 +6,40 @@ use gfx::pso::*;
 use gfx::*;
 use gfx_core::pso;
 
+/// Vertex flag drawing the quad from the RGBA image texture instead of glyph coverage.
+pub(crate) const FLAG_COLOR_IMAGE: u32 = 1;
+/// Vertex flag drawing the quad in its solid colour.
+pub(crate) const FLAG_SOLID: u32 = 1 << 1;
+/// Vertex flag multiplying the quad's colours by its palette colour.
+pub(crate) const FLAG_PALETTE: u32 = 1 << 2;
+/// Vertex flag moving the transformed quad so it lies on whole target pixels.
+pub(crate) const FLAG_SNAP: u32 = 1 << 3;
+/// Vertex flag multiplying coverage by the alpha of a clip mask image.
+pub(crate) const FLAG_MASK: u32 = 1 << 4;
+
+/// Depth target, also using the stencil buffer with a `StencilMode`.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
-pub struct RawDepthTarget;
+pub struct RawDepthTarget {
+    /// Stencil reference value, if the stencil buffer is used
+    stencil_reference: Option<u8>,
+}
 
 impl<'a> DataLink<'a> for RawDepthTarget {
-    type Init = (format::Format, state::Depth);
+    type Init = (format::Format, state::Depth, Option<StencilMode>);
     fn new() -> Self {
-        RawDepthTarget
+        RawDepthTarget {
+            stencil_reference: None,
+        }
     }
     fn is_active(&self) -> bool {
         true
     }
     fn link_depth_stencil(&mut self, init: &Self::Init) -> Option<pso::DepthStencilDesc> {
-        Some((init.0, init.1.into()))
+        self.stencil_reference = init.2.map(StencilMode::reference);
+        match init.2 {
+            Some(stencil) => Some((init.0, (init.1, stencil.state()).into())),
+            None => Some((init.0, init.1.into())),
+        }
     }
 }
 
@@ -32,8 +53,15 @@ impl<R: Resources> DataBind<R> for RawDepthTarget {
         _: &mut AccessInfo<R>,
     ) {
         let dsv = data;
-        out.pixel_targets
-            .add_depth_stencil(man.ref_dsv(dsv), true, false, dsv.get_dimensions());
+        out.pixel_targets.add_depth_stencil(
+            man.ref_dsv(dsv),
+            true,
+            self.stencil_reference.is_some(),
+            dsv.get_dimensions(),
+        );
+        if let Some(reference) = self.stencil_reference {
+            out.ref_values.stencil = (reference, reference);
+        }
     }
 }
 
@@ -45,8 +73,25 @@ gfx_defines! {
         /// texture position
         tex_left_top: [f32; 2] = "tex_left_top",
         tex_right_bottom: [f32; 2] = "tex_right_bottom",
-        /// text color
-        color: [f32; 4] = "color",
+        /// clip mask image texel position, with `FLAG_MASK`
+        mask_left_top: [f32; 2] = "mask_left_top",
+        mask_right_bottom: [f32; 2] = "mask_right_bottom",
+        /// text color at each corner
+        color_left_top: [f32; 4] = "color_left_top",
+        color_right_top: [f32; 4] = "color_right_top",
+        color_left_bottom: [f32; 4] = "color_left_bottom",
+        color_right_bottom: [f32; 4] = "color_right_bottom",
+        /// `FLAG_*` bits
+        flags: u32 = "flags",
+        /// palette colour index, with `FLAG_PALETTE`
+        palette_index: u32 = "palette_index",
+        /// tint colour index, 0 being untinted
+        tint_index: u32 = "tint_index",
+        /// section index + 1, glyph index & tag written by the picking draw, 0s if not a glyph
+        pick: [u32; 3] = "pick",
+        /// screen position & z of a whole pixel of the section, moved onto a target pixel
+        /// with `FLAG_SNAP`, moving the section's quads with it
+        snap_origin: [f32; 3] = "snap_origin",
     }
 }
 
@@ -54,8 +99,19 @@ gfx_pipeline_base!( glyph_pipe {
     vbuf: InstanceBuffer<GlyphVertex>,
     font_tex: gfx::pso::resource::TextureSampler<TexFormView>,
     transform: Global<[[f32; 4]; 4]>,
+    target_size: Global<[f32; 2]>,
+    viewport: Global<[f32; 4]>,
+    coverage_gamma: Global<f32>,
+    coverage_contrast: Global<f32>,
+    time: Global<f32>,
+    user_vec4: Global<[f32; 4]>,
+    user_tex: gfx::pso::resource::TextureSampler<[f32; 4]>,
+    image_tex: gfx::pso::resource::TextureSampler<[f32; 4]>,
+    palette_tex: gfx::pso::resource::TextureSampler<[f32; 4]>,
+    tint_tex: gfx::pso::resource::TextureSampler<[f32; 4]>,
     out: RawRenderTarget,
     out_depth: RawDepthTarget,
+    scissor: Scissor,
 });
 
 impl<'a> glyph_pipe::Init<'a> {
@@ -63,18 +119,63 @@ impl<'a> glyph_pipe::Init<'a> {
         color_format: format::Format,
         depth_format: format::Format,
         depth_test: state::Depth,
+        stencil: Option<StencilMode>,
+        blend: state::Blend,
     ) -> Self {
+        // masks only write the stencil buffer
+        let color_mask = match stencil {
+            Some(StencilMode::Mask { .. }) => state::ColorMask::empty(),
+            _ => state::ColorMask::all(),
+        };
         glyph_pipe::Init {
             vbuf: (),
             font_tex: "font_tex",
             transform: "transform",
-            out: (
-                "Target0",
-                color_format,
-                state::ColorMask::all(),
-                Some(preset::blend::ALPHA),
-            ),
-            out_depth: (depth_format, depth_test),
+            target_size: "target_size",
+            viewport: "viewport",
+            coverage_gamma: "coverage_gamma",
+            coverage_contrast: "coverage_contrast",
+            time: "time",
+            user_vec4: "user_vec4",
+            user_tex: "user_tex",
+            image_tex: "image_tex",
+            palette_tex: "palette_tex",
+            tint_tex: "tint_tex",
+            out: ("Target0", color_format, color_mask, Some(blend)),
+            out_depth: (depth_format, depth_test, stencil),
+            scissor: (),
+        }
+    }
+}
+
+gfx_pipeline_base!( pick_pipe {
+    vbuf: InstanceBuffer<GlyphVertex>,
+    font_tex: gfx::pso::resource::TextureSampler<TexFormView>,
+    transform: Global<[[f32; 4]; 4]>,
+    target_size: Global<[f32; 2]>,
+    viewport: Global<[f32; 4]>,
+    image_tex: gfx::pso::resource::TextureSampler<[f32; 4]>,
+    palette_tex: gfx::pso::resource::TextureSampler<[f32; 4]>,
+    tint_tex: gfx::pso::resource::TextureSampler<[f32; 4]>,
+    out: RenderTarget<PickFormat>,
+    out_depth: RawDepthTarget,
+    scissor: Scissor,
+});
+
+impl<'a> pick_pipe::Init<'a> {
+    pub fn new(depth_format: format::Format, depth_test: state::Depth) -> Self {
+        pick_pipe::Init {
+            vbuf: (),
+            font_tex: "font_tex",
+            transform: "transform",
+            target_size: "target_size",
+            viewport: "viewport",
+            image_tex: "image_tex",
+            palette_tex: "palette_tex",
+            tint_tex: "tint_tex",
+            out: "Target0",
+            out_depth: (depth_format, depth_test, None),
+            scissor: (),
         }
     }
 }
@@ -108,7 +209,7 @@ impl<R: Resources, T: Formatted> RawAndFormat for DepthStencilView<R, T> {
     }
 }
 
-impl<'a, R> RawAndFormat for (&'a R, Format) {
+impl<R> RawAndFormat for (&R, Format) {
     type Raw = R;
     fn as_raw(&self) -> &Self::Raw {
         self.0
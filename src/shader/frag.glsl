@@ -1,16 +1,56 @@
 #version 150
 
+const uint FLAG_COLOR_IMAGE = 1u;
+const uint FLAG_SOLID = 2u;
+const uint FLAG_MASK = 16u;
+
 uniform sampler2D font_tex;
+uniform sampler2D image_tex;
+uniform float coverage_gamma;
+uniform float coverage_contrast;
 
 in vec2 f_tex_pos;
+in vec2 f_mask_pos;
 in vec4 f_color;
+flat in uint f_flags;
 
 out vec4 Target0;
 
-void main() {
+// adjusts raw atlas coverage so text weight doesn't depend on the target's blending space
+float adjust_coverage(float coverage, vec3 color) {
+    float luminance = dot(color, vec3(0.2126, 0.7152, 0.0722));
+    coverage = pow(coverage, mix(1.0 / coverage_gamma, coverage_gamma, luminance));
+    return coverage * (coverage_contrast + 1.0) / (coverage * coverage_contrast + 1.0);
+}
+
+vec4 unmasked_color() {
+    if ((f_flags & FLAG_SOLID) != 0u) {
+        return f_color;
+    }
+    if ((f_flags & FLAG_COLOR_IMAGE) != 0u) {
+        // image texture positions are in texels
+        vec4 color = f_color * texture(image_tex, f_tex_pos / vec2(textureSize(image_tex, 0)));
+        if (color.a <= 0.0) {
+            discard;
+        }
+        return color;
+    }
+
     float alpha = texture(font_tex, f_tex_pos).r;
     if (alpha <= 0.0) {
         discard;
     }
-    Target0 = f_color * vec4(1.0, 1.0, 1.0, alpha);
+    alpha = adjust_coverage(alpha, f_color.rgb);
+    return f_color * vec4(1.0, 1.0, 1.0, alpha);
+}
+
+void main() {
+    Target0 = unmasked_color();
+    if ((f_flags & FLAG_MASK) != 0u) {
+        // mask positions are texels of the image texture
+        Target0.a *= texture(image_tex, f_mask_pos / vec2(textureSize(image_tex, 0))).a;
+        if (Target0.a <= 0.0) {
+            discard;
+        }
+    }
 }
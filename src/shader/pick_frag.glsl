@@ -0,0 +1,37 @@
+#version 150
+
+const uint FLAG_COLOR_IMAGE = 1u;
+const uint FLAG_MASK = 16u;
+
+uniform sampler2D font_tex;
+uniform sampler2D image_tex;
+
+in vec2 f_tex_pos;
+in vec2 f_mask_pos;
+flat in uint f_flags;
+flat in uvec3 f_pick;
+
+out uvec4 Target0;
+
+// writes the section & glyph of pixels at least half covered by a glyph
+void main() {
+    if (f_pick.x == 0u) {
+        // not a glyph
+        discard;
+    }
+
+    float alpha;
+    if ((f_flags & FLAG_COLOR_IMAGE) != 0u) {
+        alpha = texture(image_tex, f_tex_pos / vec2(textureSize(image_tex, 0))).a;
+    } else {
+        alpha = texture(font_tex, f_tex_pos).r;
+    }
+    if ((f_flags & FLAG_MASK) != 0u) {
+        alpha *= texture(image_tex, f_mask_pos / vec2(textureSize(image_tex, 0))).a;
+    }
+    if (alpha < 0.5) {
+        discard;
+    }
+
+    Target0 = uvec4(f_pick, 0u);
+}
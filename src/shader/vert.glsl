@@ -1,15 +1,44 @@
 #version 150
 
+const uint FLAG_PALETTE = 4u;
+const uint FLAG_SNAP = 8u;
+
 uniform mat4 transform;
+uniform vec2 target_size;
+// x, y, width, height in target pixels, origin at the top left
+uniform vec4 viewport;
+uniform sampler2D palette_tex;
+uniform sampler2D tint_tex;
 
 in vec3 left_top;
 in vec2 right_bottom;
 in vec2 tex_left_top;
 in vec2 tex_right_bottom;
-in vec4 color;
+in vec2 mask_left_top;
+in vec2 mask_right_bottom;
+in vec4 color_left_top;
+in vec4 color_right_top;
+in vec4 color_left_bottom;
+in vec4 color_right_bottom;
+in uint flags;
+in uint palette_index;
+in uint tint_index;
+in uvec3 pick;
+in vec3 snap_origin;
 
 out vec2 f_tex_pos;
+out vec2 f_mask_pos;
 out vec4 f_color;
+flat out uint f_flags;
+flat out uvec3 f_pick;
+
+// maps a clip space position in the viewport to one in the whole target
+vec4 to_target(vec4 position) {
+    vec2 scale = viewport.zw / target_size;
+    vec2 center = (viewport.xy + 0.5 * viewport.zw) / target_size;
+    vec2 offset = vec2(2.0 * center.x - 1.0, 1.0 - 2.0 * center.y);
+    return vec4(position.xy * scale + offset * position.w, position.zw);
+}
 
 // generate positional data based on vertex ID
 void main() {
@@ -23,21 +52,49 @@ void main() {
         case 0:
             pos = vec2(left, top);
             f_tex_pos = tex_left_top;
+            f_mask_pos = mask_left_top;
+            f_color = color_left_top;
             break;
         case 1:
             pos = vec2(right, top);
             f_tex_pos = vec2(tex_right_bottom.x, tex_left_top.y);
+            f_mask_pos = vec2(mask_right_bottom.x, mask_left_top.y);
+            f_color = color_right_top;
             break;
         case 2:
             pos = vec2(left, bottom);
             f_tex_pos = vec2(tex_left_top.x, tex_right_bottom.y);
+            f_mask_pos = vec2(mask_left_top.x, mask_right_bottom.y);
+            f_color = color_left_bottom;
             break;
         case 3:
             pos = vec2(right, bottom);
             f_tex_pos = tex_right_bottom;
+            f_mask_pos = mask_right_bottom;
+            f_color = color_right_bottom;
             break;
     }
 
-    f_color = color;
-    gl_Position = transform * vec4(pos, left_top.z, 1.0);
+    if ((flags & FLAG_PALETTE) != 0u) {
+        // indices outside the palette use white
+        if (int(palette_index) < textureSize(palette_tex, 0).x) {
+            f_color *= texelFetch(palette_tex, ivec2(palette_index, 0), 0);
+        }
+    }
+
+    f_color *= texelFetch(tint_tex, ivec2(tint_index, 0), 0);
+
+    f_flags = flags;
+    f_pick = pick;
+    gl_Position = to_target(transform * vec4(pos, left_top.z, 1.0));
+
+    if ((flags & FLAG_SNAP) != 0u) {
+        // move the section's quads together so its snap origin lies on a pixel corner, glyph
+        // quads lie on whole pixels relative to it so then do too. Clipped quads & mask
+        // positions move with their neighbours.
+        vec4 origin = to_target(transform * vec4(snap_origin, 1.0));
+        vec2 origin_pixels = (origin.xy / origin.w * 0.5 + 0.5) * target_size;
+        vec2 offset = (round(origin_pixels) - origin_pixels) / target_size * 2.0;
+        gl_Position.xy += offset * gl_Position.w;
+    }
 }
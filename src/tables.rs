@@ -0,0 +1,51 @@
+//! Reading of font tables not exposed by rusttype.
+
+/// Finds a table in the font, or in the first font of a font collection.
+pub(crate) fn find_table<'a>(font_data: &'a [u8], tag: &[u8; 4]) -> Option<&'a [u8]> {
+    let font_offset = if font_data.get(..4)? == b"ttcf" {
+        read_u32(font_data, 12)? as usize
+    } else {
+        0
+    };
+    let table_count = read_u16(font_data, font_offset + 4)? as usize;
+    (0..table_count).find_map(|index| {
+        let record = font_offset + 12 + index * 16;
+        if font_data.get(record..record + 4)? != tag {
+            return None;
+        }
+        let offset = read_u32(font_data, record + 8)? as usize;
+        let length = read_u32(font_data, record + 12)? as usize;
+        font_data.get(offset..offset + length)
+    })
+}
+
+pub(crate) fn read_u16(data: &[u8], offset: usize) -> Option<u16> {
+    let bytes = data.get(offset..offset + 2)?;
+    Some(u16::from(bytes[0]) << 8 | u16::from(bytes[1]))
+}
+
+pub(crate) fn read_u32(data: &[u8], offset: usize) -> Option<u32> {
+    Some(u32::from(read_u16(data, offset)?) << 16 | u32::from(read_u16(data, offset + 2)?))
+}
+
+pub(crate) fn read_i16(data: &[u8], offset: usize) -> Option<i16> {
+    read_u16(data, offset).map(|value| value as i16)
+}
+
+/// Font data containing the tables, for tests of table parsing.
+#[cfg(test)]
+pub(crate) fn test_font(tables: &[(&[u8; 4], &[u8])]) -> Vec<u8> {
+    let mut font = vec![0, 1, 0, 0, 0, tables.len() as u8, 0, 0, 0, 0, 0, 0];
+    let mut offset = font.len() + tables.len() * 16;
+    for (tag, table) in tables {
+        font.extend_from_slice(&tag[..]);
+        font.extend_from_slice(&[0; 4]);
+        font.extend_from_slice(&(offset as u32).to_be_bytes());
+        font.extend_from_slice(&(table.len() as u32).to_be_bytes());
+        offset += table.len();
+    }
+    for (_, table) in tables {
+        font.extend_from_slice(table);
+    }
+    font
+}
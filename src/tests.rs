@@ -0,0 +1,797 @@
+//! Tests of vertex generation, mostly through a brush using a factory that creates dummy
+//! resources.
+//!
+//! gfx has no software backend to draw with, so these check the generated vertices rather than
+//! drawn pixels.
+use super::*;
+use gfx_core::{
+    buffer,
+    dummy::{DummyCommandBuffer, DummyResources},
+    factory::{ResourceViewError, TargetViewError},
+    format::Formatted,
+    handle::{Manager, Producer},
+    mapping,
+    memory::{Bind, Usage},
+    pso, shade, Capabilities, Factory, ShaderSet,
+};
+use std::{cell::RefCell, rc::Rc};
+
+type R = DummyResources;
+
+const FONT_DATA: &[u8] = include_bytes!("../examples/DejaVuSans.ttf");
+
+lazy_static! {
+    static ref FONT: Font<'static> = Font::from_bytes(FONT_DATA).unwrap();
+}
+
+/// Creates resources of the dummy backend, which can't be mapped or drawn with.
+pub(crate) struct TestFactory {
+    capabilities: Capabilities,
+    manager: Manager<R>,
+    /// Descriptors of the pipeline states created
+    pipelines: Rc<RefCell<Vec<pso::Descriptor>>>,
+}
+
+impl TestFactory {
+    pub(crate) fn new() -> Self {
+        TestFactory {
+            capabilities: Capabilities {
+                max_vertex_count: 0,
+                max_index_count: 0,
+                max_texture_size: 4096,
+                max_patch_size: 0,
+                instance_base_supported: true,
+                instance_call_supported: true,
+                instance_rate_supported: true,
+                vertex_base_supported: true,
+                srgb_color_supported: true,
+                constant_buffer_supported: true,
+                unordered_access_view_supported: false,
+                separate_blending_slots_supported: false,
+                copy_buffer_supported: true,
+            },
+            manager: Manager::new(),
+            pipelines: Rc::default(),
+        }
+    }
+}
+
+impl gfx::Factory<R> for TestFactory {
+    fn get_capabilities(&self) -> &Capabilities {
+        &self.capabilities
+    }
+
+    fn create_buffer_raw(
+        &mut self,
+        info: buffer::Info,
+    ) -> Result<handle::RawBuffer<R>, buffer::CreationError> {
+        Ok(self.manager.make_buffer((), info, None))
+    }
+
+    fn create_buffer_immutable_raw(
+        &mut self,
+        data: &[u8],
+        stride: usize,
+        role: buffer::Role,
+        bind: Bind,
+    ) -> Result<handle::RawBuffer<R>, buffer::CreationError> {
+        let info = buffer::Info {
+            role,
+            usage: Usage::Data,
+            bind,
+            size: data.len(),
+            stride,
+        };
+        self.create_buffer_raw(info)
+    }
+
+    fn create_pipeline_state_raw(
+        &mut self,
+        program: &handle::Program<R>,
+        descriptor: &pso::Descriptor,
+    ) -> Result<handle::RawPipelineState<R>, pso::CreationError> {
+        self.pipelines.borrow_mut().push(*descriptor);
+        Ok(self.manager.make_pso((), program))
+    }
+
+    fn create_program(
+        &mut self,
+        _: &ShaderSet<R>,
+    ) -> Result<handle::Program<R>, shade::CreateProgramError> {
+        let info = shade::ProgramInfo {
+            vertex_attributes: vec![],
+            globals: vec![],
+            constant_buffers: vec![],
+            textures: vec![],
+            unordereds: vec![],
+            samplers: vec![],
+            outputs: vec![],
+            output_depth: false,
+            knows_outputs: false,
+        };
+        Ok(self.manager.make_program((), info))
+    }
+
+    fn create_shader(
+        &mut self,
+        _: shade::Stage,
+        _: &[u8],
+    ) -> Result<handle::Shader<R>, shade::CreateShaderError> {
+        Ok(self.manager.make_shader(()))
+    }
+
+    fn create_sampler(&mut self, info: texture::SamplerInfo) -> handle::Sampler<R> {
+        self.manager.make_sampler((), info)
+    }
+
+    fn read_mapping<'b, T: Copy>(
+        &mut self,
+        _: &'b handle::Buffer<R, T>,
+    ) -> Result<mapping::Reader<'b, R, T>, mapping::Error> {
+        Err(mapping::Error::AccessOverlap)
+    }
+
+    fn write_mapping<'b, T: Copy>(
+        &mut self,
+        _: &'b handle::Buffer<R, T>,
+    ) -> Result<mapping::Writer<'b, R, T>, mapping::Error> {
+        Err(mapping::Error::AccessOverlap)
+    }
+
+    fn create_texture_raw(
+        &mut self,
+        info: texture::Info,
+        _: Option<format::ChannelType>,
+        _: Option<(&[&[u8]], texture::Mipmap)>,
+    ) -> Result<handle::RawTexture<R>, texture::CreationError> {
+        Ok(self.manager.make_texture((), info))
+    }
+
+    fn view_buffer_as_shader_resource_raw(
+        &mut self,
+        buffer: &handle::RawBuffer<R>,
+        _: format::Format,
+    ) -> Result<handle::RawShaderResourceView<R>, ResourceViewError> {
+        Ok(self.manager.make_buffer_srv((), buffer))
+    }
+
+    fn view_buffer_as_unordered_access_raw(
+        &mut self,
+        buffer: &handle::RawBuffer<R>,
+    ) -> Result<handle::RawUnorderedAccessView<R>, ResourceViewError> {
+        Ok(self.manager.make_buffer_uav((), buffer))
+    }
+
+    fn view_texture_as_shader_resource_raw(
+        &mut self,
+        texture: &handle::RawTexture<R>,
+        _: texture::ResourceDesc,
+    ) -> Result<handle::RawShaderResourceView<R>, ResourceViewError> {
+        Ok(self.manager.make_texture_srv((), texture))
+    }
+
+    fn view_texture_as_unordered_access_raw(
+        &mut self,
+        texture: &handle::RawTexture<R>,
+    ) -> Result<handle::RawUnorderedAccessView<R>, ResourceViewError> {
+        Ok(self.manager.make_texture_uav((), texture))
+    }
+
+    fn view_texture_as_render_target_raw(
+        &mut self,
+        texture: &handle::RawTexture<R>,
+        desc: texture::RenderDesc,
+    ) -> Result<handle::RawRenderTargetView<R>, TargetViewError> {
+        let dimensions = texture.get_info().kind.get_level_dimensions(desc.level);
+        Ok(self.manager.make_rtv((), texture, dimensions))
+    }
+
+    fn view_texture_as_depth_stencil_raw(
+        &mut self,
+        texture: &handle::RawTexture<R>,
+        desc: texture::DepthStencilDesc,
+    ) -> Result<handle::RawDepthStencilView<R>, TargetViewError> {
+        let dimensions = texture.get_info().kind.get_level_dimensions(desc.level);
+        Ok(self.manager.make_dsv((), texture, dimensions))
+    }
+}
+
+fn glyph_id(c: char) -> GlyphId {
+    FONT.glyph(c).id()
+}
+
+/// A section of a glyph per character at 20px, with its z override.
+fn section(glyphs: &[(char, Option<f32>)], z: f32) -> Section<'static> {
+    let glyphs = glyphs
+        .iter()
+        .enumerate()
+        .map(|(index, &(c, glyph_z))| LayoutGlyph {
+            color: [0.0, 0.0, 0.0, 1.0],
+            font_id: 0,
+            glyph: FONT
+                .glyph(c)
+                .scaled(Scale::uniform(20.0))
+                .positioned(point(10.0 + index as f32 * 20.0, 30.0)),
+            palette_index: None,
+            tag: 0,
+            z: glyph_z,
+        })
+        .collect();
+    Section {
+        glyphs,
+        z,
+        ..Section::default()
+    }
+}
+
+/// COLR & CPAL tables drawing `base` as `layers`, each with a palette entry or `0xFFFF` for
+/// the text colour, with a single palette of `colors`.
+fn color_layers(base: GlyphId, layers: &[(GlyphId, u16)], colors: &[[u8; 4]]) -> ColorLayers {
+    let mut colr = vec![0, 0, 0, 1, 0, 0, 0, 14, 0, 0, 0, 20];
+    colr.extend(&(layers.len() as u16).to_be_bytes());
+    colr.extend(&(base.0 as u16).to_be_bytes());
+    colr.extend(&[0, 0]);
+    colr.extend(&(layers.len() as u16).to_be_bytes());
+    for &(glyph, entry) in layers {
+        colr.extend(&(glyph.0 as u16).to_be_bytes());
+        colr.extend(&entry.to_be_bytes());
+    }
+
+    let mut cpal = vec![0, 0];
+    cpal.extend(&(colors.len() as u16).to_be_bytes());
+    cpal.extend(&[0, 1]);
+    cpal.extend(&(colors.len() as u16).to_be_bytes());
+    cpal.extend(&[0, 0, 0, 14, 0, 0]);
+    for &[r, g, b, a] in colors {
+        cpal.extend(&[b, g, r, a]);
+    }
+
+    let font = tables::test_font(&[(b"COLR", &colr), (b"CPAL", &cpal)]);
+    ColorLayers::parse(&font).unwrap()
+}
+
+fn brush() -> GlyphBrush<'static, R, TestFactory> {
+    GlyphBrushBuilder::using_font_bytes(FONT_DATA).build(TestFactory::new())
+}
+
+/// Render & depth targets to draw to.
+fn targets(
+    factory: &mut TestFactory,
+) -> (
+    handle::RenderTargetView<R, format::Srgba8>,
+    handle::DepthStencilView<R, format::Depth>,
+) {
+    let (_, _, target) = factory
+        .create_render_target::<format::Srgba8>(200, 100)
+        .unwrap();
+    let depth_target = factory
+        .create_depth_stencil_view_only::<format::Depth>(200, 100)
+        .unwrap();
+    (target, depth_target)
+}
+
+/// Generates the vertices of sections like a draw, caching their glyphs first.
+fn vertices(
+    brush: &mut GlyphBrush<'static, R, TestFactory>,
+    sections: &[Section<'static>],
+) -> Vec<GlyphVertex> {
+    let mut encoder: gfx::Encoder<R, _> = DummyCommandBuffer.into();
+    brush.cache_glyphs(sections, &mut encoder).unwrap();
+    brush.section_vertices(sections, (200.0, 100.0))
+}
+
+/// Vertices of 'A' drawn as COLR layers, 'B' from the glyph texture & 'C' as a colour image,
+/// as `(glyph index, colour image, z)`.
+#[test]
+fn glyph_z_overrides_section_z() {
+    let mut brush = brush();
+    let layers = color_layers(
+        glyph_id('A'),
+        &[(glyph_id('O'), 0), (glyph_id('I'), 0xFFFF)],
+        &[[255, 0, 0, 255]],
+    );
+    brush.set_color_layers(0, layers);
+    brush
+        .add_color_glyph(
+            0,
+            glyph_id('C'),
+            ColorGlyph {
+                data: &[[255, 0, 0, 255]; 4],
+                width: 2,
+                height: 2,
+                ppem: 2.0,
+                bearing: Vector { x: 0.0, y: -2.0 },
+            },
+        )
+        .unwrap();
+
+    type Case<'a> = (&'a [(char, Option<f32>)], &'a [(u32, bool, f32)]);
+    let cases: &[Case] = &[
+        (
+            &[('A', Some(0.25)), ('A', None)],
+            &[
+                (0, false, 0.25),
+                (0, false, 0.25),
+                (1, false, 0.75),
+                (1, false, 0.75),
+            ],
+        ),
+        (
+            &[('B', Some(0.25)), ('B', None), ('B', Some(-0.5))],
+            &[(0, false, 0.25), (1, false, 0.75), (2, false, -0.5)],
+        ),
+        (
+            &[('C', Some(0.25)), ('C', None), ('B', Some(0.5))],
+            &[(0, true, 0.25), (1, true, 0.75), (2, false, 0.5)],
+        ),
+    ];
+    for &(glyphs, expected) in cases {
+        let verts = vertices(&mut brush, &[section(glyphs, 0.75)]);
+        let depths: Vec<_> = verts
+            .iter()
+            .map(|vertex| {
+                let color_image = vertex.flags & FLAG_COLOR_IMAGE != 0;
+                (vertex.pick[1], color_image, vertex.left_top[2])
+            })
+            .collect();
+        assert_eq!(depths, expected, "{:?}", glyphs);
+    }
+}
+
+/// CPAL colours are resolved into layer glyph colours, so only the user palette takes rows
+/// of the colour tables, & setting it again reuses them.
+#[test]
+fn color_layers_and_palettes_reuse_table_rows() {
+    let mut brush = brush();
+    let (target, depth_target) = targets(&mut brush.factory);
+    let mut encoder: gfx::Encoder<R, _> = DummyCommandBuffer.into();
+    let table_lens =
+        |brush: &GlyphBrush<'_, R, TestFactory>| (brush.palette.len(), brush.tints.len());
+    let initial = table_lens(&brush);
+
+    for palette in 0..3 {
+        let layers = color_layers(
+            glyph_id('A'),
+            &[(glyph_id('O'), 0), (glyph_id('I'), 1)],
+            &[[255, 0, 0, 255], [0, 0, 255, 255]],
+        );
+        brush.set_color_layers(0, layers);
+        brush.set_palette(&[[0.5; 4]; 3]).unwrap();
+        let mut section = section(&[('A', None)], 0.0);
+        section.cpal_palette = palette;
+        brush.queue_section(section);
+        brush
+            .draw_queued(&mut encoder, &target, &depth_target)
+            .unwrap();
+        assert_eq!(table_lens(&brush), initial);
+    }
+
+    brush.set_palette(&[[0.5; 4]; 20]).unwrap();
+    let grown = brush.palette.len();
+    brush.set_palette(&[[0.5; 4]; 3]).unwrap();
+    brush.set_palette(&[[0.5; 4]; 20]).unwrap();
+    assert_eq!(brush.palette.len(), grown);
+}
+
+/// Checks the depth state the pipeline is created with & the order drawn vertices are sorted
+/// in. The dummy backend can't tell whether drawn glyphs pass the depth test.
+#[test]
+fn glyph_z_is_depth_tested() {
+    let mut brush = GlyphBrushBuilder::using_font_bytes(FONT_DATA)
+        .depth_test(gfx::preset::depth::LESS_EQUAL_WRITE)
+        .depth_sort(DepthSort::FrontToBack)
+        .build(TestFactory::new());
+    let pipelines = brush.factory.pipelines.clone();
+    let (target, depth_target) = targets(&mut brush.factory);
+    let mut encoder: gfx::Encoder<R, _> = DummyCommandBuffer.into();
+
+    brush.queue_section(section(&[('A', Some(0.25)), ('B', None)], 0.75));
+    brush.queue_section(section(&[('C', None)], 0.5));
+    brush
+        .draw_queued(&mut encoder, &target, &depth_target)
+        .unwrap();
+
+    let verts = brush.section_vertices(&brush.drawn_sections, (200.0, 100.0));
+    let depths: Vec<_> = verts.iter().map(|vertex| vertex.left_top[2]).collect();
+    assert_eq!(depths, [0.25, 0.5, 0.75]);
+
+    let pipelines = pipelines.borrow();
+    let (depth_format, depth_stencil) = pipelines.last().unwrap().depth_stencil.unwrap();
+    assert_eq!(depth_format, format::Depth::get_format());
+    assert_eq!(
+        depth_stencil.depth,
+        Some(gfx::preset::depth::LESS_EQUAL_WRITE)
+    );
+}
+
+#[test]
+fn bake_draws_premultiplied_with_the_brush_stencil() {
+    let mut brush = GlyphBrushBuilder::using_font_bytes(FONT_DATA)
+        .stencil(StencilMode::ClipTo { reference: 1 })
+        .blend(gfx::preset::blend::ADD)
+        .build(TestFactory::new());
+    let pipelines = brush.factory.pipelines.clone();
+    let mut encoder: gfx::Encoder<R, _> = DummyCommandBuffer.into();
+
+    brush.queue_section(section(&[('A', None), ('B', None)], 0.0));
+    let baked = brush.bake_to_texture(&mut encoder).unwrap().unwrap();
+    assert!(baked.size.0 > 0 && baked.size.1 > 0);
+    assert_eq!(brush.blend, gfx::preset::blend::ADD);
+
+    let pipelines = pipelines.borrow();
+    let descriptor = pipelines.last().unwrap();
+    let (depth_format, depth_stencil) = descriptor.depth_stencil.unwrap();
+    assert_eq!(depth_format, format::DepthStencil::get_format());
+    assert!(depth_stencil.front.is_some());
+    let (_, color) = descriptor.color_targets[0].unwrap();
+    let alpha = color.alpha.unwrap();
+    assert_eq!(alpha.source, gfx::state::Factor::One);
+    assert_eq!(
+        alpha.destination,
+        gfx::state::Factor::OneMinus(gfx::state::BlendValue::SourceAlpha)
+    );
+}
+
+#[test]
+fn bake_larger_than_the_max_texture_size_fails() {
+    let mut brush = brush();
+    let mut encoder: gfx::Encoder<R, _> = DummyCommandBuffer.into();
+
+    let mut wide = section(&[('A', None), ('B', None)], 0.0);
+    let b = wide.glyphs[1].glyph.clone();
+    wide.glyphs[1].glyph = b.into_unpositioned().positioned(point(5000.0, 30.0));
+    brush.queue_section(wide);
+
+    assert!(brush.bake_to_texture(&mut encoder).is_err());
+    assert_eq!(brush.sections.len(), 1);
+}
+
+#[test]
+fn viewports_outside_the_target_fail() {
+    let mut brush = brush();
+    let (target, depth_target) = targets(&mut brush.factory);
+    let mut encoder: gfx::Encoder<R, _> = DummyCommandBuffer.into();
+    brush.queue_section(section(&[('A', None)], 0.0));
+
+    let viewport = |min: (u32, u32), max: (u32, u32)| Rect {
+        min: point(min.0, min.1),
+        max: point(max.0, max.1),
+    };
+    for &(min, max) in &[
+        ((100, 0), (50, 100)),
+        ((0, 60), (200, 40)),
+        ((0, 0), (201, 100)),
+        ((0, 0), (200, 65_636)),
+    ] {
+        assert!(brush
+            .draw_queued_in_viewport(
+                IDENTITY_MATRIX4,
+                viewport(min, max),
+                &mut encoder,
+                &target,
+                &depth_target,
+            )
+            .is_err());
+        assert_eq!(brush.sections.len(), 1);
+    }
+
+    brush
+        .draw_queued_in_viewport(
+            IDENTITY_MATRIX4,
+            viewport((100, 0), (200, 100)),
+            &mut encoder,
+            &target,
+            &depth_target,
+        )
+        .unwrap();
+    assert!(brush.sections.is_empty());
+}
+
+/// Vertices as `(kind, id, z)`: `'r'` rects & `'i'` images identified by their red, `'l'`
+/// coloured layers & `'g'` other glyphs by `section * 10 + glyph`.
+#[test]
+fn depth_sort_is_stable_across_glyphs_images_rects_and_layers() {
+    let mut brush = brush();
+    let layers = color_layers(
+        glyph_id('A'),
+        &[(glyph_id('O'), 0), (glyph_id('I'), 0xFFFF)],
+        &[[255, 0, 0, 255]],
+    );
+    brush.set_color_layers(0, layers);
+    let image = brush.add_image(2, 2, &[[255; 4]; 4]).unwrap();
+
+    let rect = |id: f32| LayoutRect {
+        color: [id, 1.0, 1.0, 1.0],
+        palette_index: None,
+        rect: Rect {
+            min: point(0.0, 40.0),
+            max: point(50.0, 42.0),
+        },
+    };
+    let image = |id: f32| LayoutImage {
+        color: [id, 1.0, 1.0, 1.0],
+        image,
+        rect: Rect {
+            min: point(60.0, 10.0),
+            max: point(70.0, 20.0),
+        },
+    };
+    let sections = [
+        Section {
+            rects: vec![rect(1.0)],
+            images: vec![image(1.0)],
+            ..section(&[('A', None), ('B', Some(0.25))], 0.5)
+        },
+        Section {
+            rects: vec![rect(2.0)],
+            ..section(&[('C', None)], 0.75)
+        },
+        Section {
+            images: vec![image(2.0)],
+            ..section(&[('D', None)], 0.5)
+        },
+    ];
+
+    let cases = [
+        (
+            DepthSort::BackToFront,
+            [
+                ('r', 2, 0.75),
+                ('g', 10, 0.75),
+                ('r', 1, 0.5),
+                ('l', 0, 0.5),
+                ('g', 0, 0.5),
+                ('i', 1, 0.5),
+                ('g', 20, 0.5),
+                ('i', 2, 0.5),
+                ('g', 1, 0.25),
+            ],
+        ),
+        (
+            DepthSort::FrontToBack,
+            [
+                ('g', 1, 0.25),
+                ('r', 1, 0.5),
+                ('l', 0, 0.5),
+                ('g', 0, 0.5),
+                ('i', 1, 0.5),
+                ('g', 20, 0.5),
+                ('i', 2, 0.5),
+                ('r', 2, 0.75),
+                ('g', 10, 0.75),
+            ],
+        ),
+    ];
+    for &(depth_sort, expected) in &cases {
+        brush.depth_sort = Some(depth_sort);
+        let order: Vec<_> = vertices(&mut brush, &sections)
+            .iter()
+            .map(|vertex| {
+                let z = vertex.left_top[2];
+                if vertex.flags & FLAG_SOLID != 0 {
+                    ('r', vertex.color_left_top[0] as u32, z)
+                } else if vertex.flags & FLAG_COLOR_IMAGE != 0 {
+                    ('i', vertex.color_left_top[0] as u32, z)
+                } else {
+                    let kind = if vertex.color_left_top[0] > 0.0 {
+                        'l'
+                    } else {
+                        'g'
+                    };
+                    (kind, (vertex.pick[0] - 1) * 10 + vertex.pick[1], z)
+                }
+            })
+            .collect();
+        assert_eq!(order, expected, "{:?}", depth_sort);
+    }
+}
+
+fn rect(min: (f32, f32), max: (f32, f32)) -> Rect<f32> {
+    Rect {
+        min: point(min.0, min.1),
+        max: point(max.0, max.1),
+    }
+}
+
+/// Bounds & first glyph position of the last queued section.
+fn last_queued(brush: &GlyphBrush<'_, R, TestFactory>) -> (Rect<f32>, Point<f32>) {
+    let section = brush.sections.last().unwrap();
+    (section.bounds, section.glyphs[0].glyph.position())
+}
+
+#[test]
+fn nested_clips_and_transforms_compose() {
+    let mut brush = brush();
+    let queue = |brush: &mut GlyphBrush<'_, R, TestFactory>| {
+        brush.queue_section(section(&[('A', None)], 0.0));
+        last_queued(brush)
+    };
+
+    brush.push_transform(Vector { x: 100.0, y: 10.0 });
+    brush.push_clip(rect((0.0, 0.0), (50.0, 50.0)));
+    assert_eq!(
+        queue(&mut brush),
+        (rect((100.0, 10.0), (150.0, 60.0)), point(110.0, 40.0))
+    );
+
+    // relative to both transforms & intersected with the outer clip
+    brush.push_transform(Vector { x: 20.0, y: 5.0 });
+    brush.push_clip(rect((0.0, 0.0), (100.0, 20.0)));
+    assert_eq!(
+        queue(&mut brush),
+        (rect((120.0, 15.0), (150.0, 35.0)), point(130.0, 45.0))
+    );
+
+    // a disjoint clip leaves nothing visible
+    brush.push_clip(rect((-50.0, -50.0), (-40.0, -40.0)));
+    let (bounds, _) = queue(&mut brush);
+    assert!(bounds.width() <= 0.0 || bounds.height() <= 0.0);
+
+    brush.pop_clip().unwrap();
+    brush.pop_clip().unwrap();
+    assert_eq!(
+        queue(&mut brush),
+        (rect((100.0, 10.0), (150.0, 60.0)), point(130.0, 45.0))
+    );
+
+    brush.pop_transform().unwrap();
+    assert_eq!(
+        queue(&mut brush),
+        (rect((100.0, 10.0), (150.0, 60.0)), point(110.0, 40.0))
+    );
+
+    brush.pop_clip().unwrap();
+    brush.pop_transform().unwrap();
+    assert_eq!(
+        queue(&mut brush),
+        (Section::default().bounds, point(10.0, 30.0))
+    );
+}
+
+#[test]
+fn unbalanced_pops_fail() {
+    let mut brush = brush();
+    assert!(brush.pop_clip().is_err());
+    assert!(brush.pop_transform().is_err());
+
+    brush.push_clip(rect((0.0, 0.0), (50.0, 50.0)));
+    brush.push_transform(Vector { x: 5.0, y: 5.0 });
+    brush.pop_clip().unwrap();
+    brush.pop_transform().unwrap();
+    assert!(brush.pop_clip().is_err());
+    assert!(brush.pop_transform().is_err());
+
+    // the stacks stay empty, so sections are queued unchanged
+    brush.queue_section(section(&[('A', None)], 0.0));
+    assert_eq!(
+        last_queued(&brush),
+        (Section::default().bounds, point(10.0, 30.0))
+    );
+}
+
+#[test]
+fn draw_prepared_keeps_the_draw_cache() {
+    let mut brush = brush();
+    let (target, depth_target) = targets(&mut brush.factory);
+    let mut encoder: gfx::Encoder<R, _> = DummyCommandBuffer.into();
+
+    brush.queue_section(section(&[('A', None)], 0.0));
+    brush
+        .draw_queued(&mut encoder, &target, &depth_target)
+        .unwrap();
+    brush.queue_section(section(&[('B', None), ('C', None)], 0.0));
+    let mut prepared = brush.prepare((200, 100));
+    brush
+        .draw_prepared(
+            &mut prepared,
+            IDENTITY_MATRIX4,
+            &mut encoder,
+            &target,
+            &depth_target,
+        )
+        .unwrap();
+
+    // adding the prepared glyphs left the drawn glyph in place
+    assert!(brush.draw_cache.is_some());
+    assert!(brush.drawn_glyphs_cached());
+    brush.draw_cached(&mut encoder);
+}
+
+#[test]
+fn draw_prepared_evicting_drawn_glyphs_clears_the_draw_cache() {
+    // room for a single large glyph
+    let mut brush = GlyphBrushBuilder::using_font_bytes(FONT_DATA)
+        .initial_cache_size((32, 32))
+        .build(TestFactory::new());
+    let (target, depth_target) = targets(&mut brush.factory);
+    let mut encoder: gfx::Encoder<R, _> = DummyCommandBuffer.into();
+    let large = |c| {
+        let mut section = section(&[(c, None)], 0.0);
+        section.glyphs[0] = section.glyphs[0].scaled(1.3);
+        section
+    };
+
+    brush.queue_section(large('W'));
+    brush
+        .draw_queued(&mut encoder, &target, &depth_target)
+        .unwrap();
+    brush.queue_section(large('M'));
+    let mut prepared = brush.prepare((200, 100));
+    brush
+        .draw_prepared(
+            &mut prepared,
+            IDENTITY_MATRIX4,
+            &mut encoder,
+            &target,
+            &depth_target,
+        )
+        .unwrap();
+
+    assert_eq!(brush.font_cache.dimensions(), (32, 32));
+    assert!(brush.draw_cache.is_none());
+    brush.draw_cached(&mut encoder);
+}
+
+#[test]
+fn snapped_section_quads_share_one_origin() {
+    let mut brush = brush();
+    let mut snapped = section(&[('A', None), ('B', None)], 0.5);
+    snapped.glyphs = snapped
+        .glyphs
+        .iter()
+        .map(|glyph| glyph.translated(Vector { x: 0.3, y: 0.4 }))
+        .collect();
+    // clips through 'B'
+    snapped.bounds.max.x = 35.0;
+    snapped.pixel_snap = Some(true);
+    snapped.rects.push(LayoutRect {
+        color: [1.0; 4],
+        palette_index: None,
+        rect: Rect {
+            min: point(0.5, 0.5),
+            max: point(60.5, 40.5),
+        },
+    });
+
+    let verts = vertices(&mut brush, &[snapped, section(&[('C', None)], 0.5)]);
+    assert_eq!(verts.len(), 4);
+    // the rounded first glyph origin (10, 30) of a 200x100 screen
+    let origin = verts[0].snap_origin;
+    for (actual, expected) in origin.iter().zip(&[-0.9, 0.4, 0.5]) {
+        assert_relative_eq!(actual, expected, epsilon = 1e-6);
+    }
+    for vertex in &verts[..3] {
+        assert_eq!(vertex.flags & FLAG_SNAP, FLAG_SNAP);
+        assert_eq!(vertex.snap_origin, origin);
+    }
+    assert_eq!(verts[3].flags & FLAG_SNAP, 0);
+}
+
+#[test]
+fn clipped_quad_colors_are_interpolated() {
+    // red, green & blue corners & a bright corner beyond 1.0, clipped to the top right quarter
+    let colors = [
+        [1.0, 0.0, 0.0, 1.0],
+        [0.0, 1.0, 0.0, 1.0],
+        [0.0, 0.0, 1.0, 1.0],
+        [2.0, 2.0, 2.0, 1.0],
+    ];
+    let section = Section {
+        bounds: Rect {
+            min: point(50.0, 0.0),
+            max: point(100.0, 50.0),
+        },
+        ..Section::default()
+    };
+    let quad = Rect {
+        min: point(0.0, 0.0),
+        max: point(100.0, 100.0),
+    };
+    let vertex = clipped_vertex(quad, quad, &section, colors, None, 0, (200.0, 100.0)).unwrap();
+
+    // vertex tops are screen bottoms, as normalized device coordinates are y up
+    assert_eq!(vertex.color_left_top, [0.75, 0.75, 0.75, 1.0]);
+    assert_eq!(vertex.color_right_top, [1.0, 1.5, 1.0, 1.0]);
+    assert_eq!(vertex.color_left_bottom, [0.5, 0.5, 0.0, 1.0]);
+    assert_eq!(vertex.color_right_bottom, [0.0, 1.0, 0.0, 1.0]);
+}
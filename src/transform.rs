@@ -0,0 +1,337 @@
+//! Transforms for [`draw_queued_with_transform`](struct.GlyphBrush.html#method.draw_queued_with_transform).
+//!
+//! Queued sections are positioned in target pixels, origin top left, & converted to normalized
+//! device coordinates before the transform is applied. [`ndc_to_pixels`](fn.ndc_to_pixels.html)
+//! undoes that conversion, so projections of pixel coordinates can be combined with it using
+//! [`pixel_transform`](fn.pixel_transform.html).
+//!
+//! Matrices are column major, as with the transform itself.
+
+/// Maps the normalized device coordinates of queued glyphs back to the pixels they were
+/// positioned at, origin top left.
+///
+/// # Example
+///
+/// ```
+/// # use gfx_glyph::*;
+/// # fn apply(m: [[f32; 4]; 4], v: [f32; 4]) -> [f32; 4] {
+/// #     let mut out = [0.0; 4];
+/// #     for (row, out) in out.iter_mut().enumerate() {
+/// #         *out = (0..4).map(|col| m[col][row] * v[col]).sum();
+/// #     }
+/// #     out
+/// # }
+/// // a glyph corner at pixel (50, 25) of a 200x100 target is queued at ndc (-0.5, 0.5)
+/// let corner = apply(ndc_to_pixels(200.0, 100.0), [-0.5, 0.5, 0.0, 1.0]);
+/// assert_eq!(corner, [50.0, 25.0, 0.0, 1.0]);
+/// ```
+pub fn ndc_to_pixels(width: f32, height: f32) -> [[f32; 4]; 4] {
+    [
+        [width / 2.0, 0.0, 0.0, 0.0],
+        [0.0, -height / 2.0, 0.0, 0.0],
+        [0.0, 0.0, 1.0, 0.0],
+        [width / 2.0, height / 2.0, 0.0, 1.0],
+    ]
+}
+
+/// Orthographic projection of pixel coordinates with the origin at the top left & y down.
+///
+/// `pixel_transform(ortho_top_left(width, height), width, height)` is the identity, i.e. the
+/// transform used by [`draw_queued`](struct.GlyphBrush.html#method.draw_queued).
+///
+/// # Example
+///
+/// ```
+/// # use gfx_glyph::*;
+/// # fn apply(m: [[f32; 4]; 4], v: [f32; 4]) -> [f32; 4] {
+/// #     let mut out = [0.0; 4];
+/// #     for (row, out) in out.iter_mut().enumerate() {
+/// #         *out = (0..4).map(|col| m[col][row] * v[col]).sum();
+/// #     }
+/// #     out
+/// # }
+/// let transform = pixel_transform(ortho_top_left(200.0, 100.0), 200.0, 100.0);
+/// // a glyph corner queued at pixel (50, 25) is drawn at the same pixel
+/// assert_eq!(apply(transform, [-0.5, 0.5, 0.0, 1.0]), [-0.5, 0.5, 0.0, 1.0]);
+/// ```
+pub fn ortho_top_left(width: f32, height: f32) -> [[f32; 4]; 4] {
+    [
+        [2.0 / width, 0.0, 0.0, 0.0],
+        [0.0, -2.0 / height, 0.0, 0.0],
+        [0.0, 0.0, 1.0, 0.0],
+        [-1.0, 1.0, 0.0, 1.0],
+    ]
+}
+
+/// Transform drawing sections positioned in pixels of a `width` x `height` target using a
+/// projection of pixel coordinates, i.e. `projection * ndc_to_pixels(width, height)`.
+pub fn pixel_transform(projection: [[f32; 4]; 4], width: f32, height: f32) -> [[f32; 4]; 4] {
+    multiply_transforms(projection, ndc_to_pixels(width, height))
+}
+
+/// Transform drawing sections as a billboard in world space, facing the camera.
+///
+/// Section pixel `(0, 0)` is placed at `anchor` & each pixel covers `units_per_pixel` world
+/// units along the camera's right & down directions, taken from the `view` matrix.
+/// Section z is ignored, the whole billboard is at the anchor's depth.
+///
+/// # Example
+///
+/// ```
+/// # use gfx_glyph::*;
+/// # fn apply(m: [[f32; 4]; 4], v: [f32; 4]) -> [f32; 4] {
+/// #     let mut out = [0.0; 4];
+/// #     for (row, out) in out.iter_mut().enumerate() {
+/// #         *out = (0..4).map(|col| m[col][row] * v[col]).sum();
+/// #     }
+/// #     out
+/// # }
+/// let identity = [
+///     [1.0, 0.0, 0.0, 0.0],
+///     [0.0, 1.0, 0.0, 0.0],
+///     [0.0, 0.0, 1.0, 0.0],
+///     [0.0, 0.0, 0.0, 1.0],
+/// ];
+/// let transform = billboard(identity, identity, [1.0, 2.0, 3.0], 0.5, 200.0, 100.0);
+/// // a glyph corner at pixel (50, 25) is 25 units right & 12.5 down from the anchor
+/// assert_eq!(apply(transform, [-0.5, 0.5, 0.0, 1.0]), [26.0, -10.5, 3.0, 1.0]);
+/// ```
+pub fn billboard(
+    view_projection: [[f32; 4]; 4],
+    view: [[f32; 4]; 4],
+    anchor: [f32; 3],
+    units_per_pixel: f32,
+    width: f32,
+    height: f32,
+) -> [[f32; 4]; 4] {
+    let right = [view[0][0], view[1][0], view[2][0]];
+    let down = [-view[0][1], -view[1][1], -view[2][1]];
+    plane(
+        view_projection,
+        anchor,
+        right,
+        down,
+        units_per_pixel,
+        width,
+        height,
+    )
+}
+
+/// Transform drawing sections lying in a plane in world space, e.g. text on a wall or the
+/// ground, rather than facing the camera.
+///
+/// Section pixel `(0, 0)` is placed at `origin` & each pixel covers `units_per_pixel` world
+/// units along the `right` & `down` unit vectors. Section z is ignored.
+///
+/// # Example
+///
+/// ```
+/// # use gfx_glyph::*;
+/// # fn apply(m: [[f32; 4]; 4], v: [f32; 4]) -> [f32; 4] {
+/// #     let mut out = [0.0; 4];
+/// #     for (row, out) in out.iter_mut().enumerate() {
+/// #         *out = (0..4).map(|col| m[col][row] * v[col]).sum();
+/// #     }
+/// #     out
+/// # }
+/// let identity = [
+///     [1.0, 0.0, 0.0, 0.0],
+///     [0.0, 1.0, 0.0, 0.0],
+///     [0.0, 0.0, 1.0, 0.0],
+///     [0.0, 0.0, 0.0, 1.0],
+/// ];
+/// // a wall facing along x, text running along z
+/// let transform = plane(
+///     identity,
+///     [1.0, 2.0, 3.0],
+///     [0.0, 0.0, 1.0],
+///     [0.0, -1.0, 0.0],
+///     0.5,
+///     200.0,
+///     100.0,
+/// );
+/// // a glyph corner at pixel (50, 25) is 25 units along the wall & 12.5 down from the origin
+/// assert_eq!(apply(transform, [-0.5, 0.5, 0.0, 1.0]), [1.0, -10.5, 28.0, 1.0]);
+/// ```
+pub fn plane(
+    view_projection: [[f32; 4]; 4],
+    origin: [f32; 3],
+    right: [f32; 3],
+    down: [f32; 3],
+    units_per_pixel: f32,
+    width: f32,
+    height: f32,
+) -> [[f32; 4]; 4] {
+    let pixels_to_world = [
+        [
+            right[0] * units_per_pixel,
+            right[1] * units_per_pixel,
+            right[2] * units_per_pixel,
+            0.0,
+        ],
+        [
+            down[0] * units_per_pixel,
+            down[1] * units_per_pixel,
+            down[2] * units_per_pixel,
+            0.0,
+        ],
+        [0.0; 4],
+        [origin[0], origin[1], origin[2], 1.0],
+    ];
+    pixel_transform(
+        multiply_transforms(view_projection, pixels_to_world),
+        width,
+        height,
+    )
+}
+
+/// Multiplies two column major matrices, `a * b` applying `b` first.
+pub fn multiply_transforms(a: [[f32; 4]; 4], b: [[f32; 4]; 4]) -> [[f32; 4]; 4] {
+    let mut product = [[0.0; 4]; 4];
+    for (col, product_col) in product.iter_mut().enumerate() {
+        for (row, value) in product_col.iter_mut().enumerate() {
+            *value = (0..4).map(|k| a[k][row] * b[col][k]).sum();
+        }
+    }
+    product
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{clipped_vertex, point, Rect, Section};
+
+    const WIDTH: f32 = 200.0;
+    const HEIGHT: f32 = 100.0;
+    const IDENTITY: [[f32; 4]; 4] = [
+        [1.0, 0.0, 0.0, 0.0],
+        [0.0, 1.0, 0.0, 0.0],
+        [0.0, 0.0, 1.0, 0.0],
+        [0.0, 0.0, 0.0, 1.0],
+    ];
+
+    /// Transformed corners of the quad of a glyph drawn over pixels (40, 20) to (60, 35), with
+    /// the section at z 0.5. Corners are in the vertex shader's order, starting with the glyph's
+    /// bottom edge as the vertex's `top` is in y up normalized coordinates.
+    fn glyph_corners(transform: [[f32; 4]; 4]) -> Vec<[f32; 4]> {
+        let section = Section {
+            z: 0.5,
+            ..Section::default()
+        };
+        let pixels = Rect {
+            min: point(40.0, 20.0),
+            max: point(60.0, 35.0),
+        };
+        let texels = Rect {
+            min: point(0.0, 0.0),
+            max: point(1.0, 1.0),
+        };
+        let vertex = clipped_vertex(
+            pixels,
+            texels,
+            &section,
+            [[1.0; 4]; 4],
+            None,
+            0,
+            (WIDTH, HEIGHT),
+        )
+        .unwrap();
+
+        let [left, top, z] = vertex.left_top;
+        let [right, bottom] = vertex.right_bottom;
+        [[left, top], [right, top], [left, bottom], [right, bottom]]
+            .iter()
+            .map(|&[x, y]| {
+                let corner = [x, y, z, 1.0];
+                let mut out = [0.0; 4];
+                for (row, out) in out.iter_mut().enumerate() {
+                    *out = (0..4).map(|col| transform[col][row] * corner[col]).sum();
+                }
+                out
+            })
+            .collect()
+    }
+
+    /// Target pixels, origin top left, of normalized device coordinates.
+    fn target_pixels(ndc: [f32; 4]) -> [f32; 3] {
+        [
+            (ndc[0] + 1.0) / 2.0 * WIDTH,
+            (1.0 - ndc[1]) / 2.0 * HEIGHT,
+            ndc[2],
+        ]
+    }
+
+    fn assert_corners(actual: Vec<[f32; 3]>, expected: [[f32; 3]; 4]) {
+        for (actual, expected) in actual.iter().zip(&expected) {
+            for (actual, expected) in actual.iter().zip(expected) {
+                assert_relative_eq!(actual, expected, epsilon = 1e-4);
+            }
+        }
+    }
+
+    #[test]
+    fn ortho_top_left_draws_at_pixels() {
+        let corners = glyph_corners(pixel_transform(
+            ortho_top_left(WIDTH, HEIGHT),
+            WIDTH,
+            HEIGHT,
+        ));
+        assert_corners(
+            corners.into_iter().map(target_pixels).collect(),
+            [
+                [40.0, 35.0, 0.5],
+                [60.0, 35.0, 0.5],
+                [40.0, 20.0, 0.5],
+                [60.0, 20.0, 0.5],
+            ],
+        );
+    }
+
+    #[test]
+    fn billboard_faces_the_camera() {
+        // camera turned to look along +x, so its right is +z & its up is +y
+        let view = [
+            [0.0, 0.0, -1.0, 0.0],
+            [0.0, 1.0, 0.0, 0.0],
+            [1.0, 0.0, 0.0, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ];
+        let transform = billboard(IDENTITY, view, [1.0, 2.0, 3.0], 0.5, WIDTH, HEIGHT);
+        let corners = glyph_corners(transform);
+        let world = |corner: [f32; 4]| [corner[0], corner[1], corner[2]];
+        assert_corners(
+            corners.into_iter().map(world).collect(),
+            [
+                [1.0, -15.5, 23.0],
+                [1.0, -15.5, 33.0],
+                [1.0, -8.0, 23.0],
+                [1.0, -8.0, 33.0],
+            ],
+        );
+    }
+
+    #[test]
+    fn plane_lies_along_its_axes() {
+        // text lying on the ground, reading along +x with its top towards -z
+        let transform = plane(
+            IDENTITY,
+            [1.0, 2.0, 3.0],
+            [1.0, 0.0, 0.0],
+            [0.0, 0.0, 1.0],
+            0.5,
+            WIDTH,
+            HEIGHT,
+        );
+        let corners = glyph_corners(transform);
+        let world = |corner: [f32; 4]| [corner[0], corner[1], corner[2]];
+        assert_corners(
+            corners.into_iter().map(world).collect(),
+            [
+                [21.0, 2.0, 20.5],
+                [31.0, 2.0, 20.5],
+                [21.0, 2.0, 13.0],
+                [31.0, 2.0, 13.0],
+            ],
+        );
+    }
+}